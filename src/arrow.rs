@@ -0,0 +1,126 @@
+//! Conversion of Delta table schemas to Arrow schemas.
+
+use std::sync::Arc;
+
+use arrow_schema::{
+    DataType as ArrowDataType, Field, Fields, Schema as ArrowSchema, SchemaRef, TimeUnit,
+};
+use delta_kernel::schema::{DataType, PrimitiveType, StructField, StructType};
+
+use crate::{DeltaSharingError, Result};
+
+/// Parse a Delta `schemaString` and convert it to an Arrow schema.
+pub(crate) fn arrow_schema_from_schema_string(schema_string: &str) -> Result<SchemaRef> {
+    let schema = serde_json::from_str::<StructType>(schema_string).map_err(|e| {
+        tracing::error!(err = ?e, "failed to parse table schema");
+        DeltaSharingError::parse_response("failed to parse table schema").with_source(e)
+    })?;
+    let fields = schema
+        .fields()
+        .into_iter()
+        .map(arrow_field)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Arc::new(ArrowSchema::new(fields)))
+}
+
+fn arrow_field(field: &StructField) -> Result<Field> {
+    Ok(Field::new(
+        field.name(),
+        arrow_data_type(field.data_type())?,
+        field.is_nullable(),
+    ))
+}
+
+fn arrow_data_type(data_type: &DataType) -> Result<ArrowDataType> {
+    let arrow_type = match data_type {
+        DataType::Primitive(p) => match p {
+            PrimitiveType::String => ArrowDataType::Utf8,
+            PrimitiveType::Long => ArrowDataType::Int64,
+            PrimitiveType::Integer => ArrowDataType::Int32,
+            PrimitiveType::Short => ArrowDataType::Int16,
+            PrimitiveType::Byte => ArrowDataType::Int8,
+            PrimitiveType::Float => ArrowDataType::Float32,
+            PrimitiveType::Double => ArrowDataType::Float64,
+            PrimitiveType::Boolean => ArrowDataType::Boolean,
+            PrimitiveType::Binary => ArrowDataType::Binary,
+            PrimitiveType::Date => ArrowDataType::Date32,
+            PrimitiveType::Timestamp => {
+                ArrowDataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+            }
+            PrimitiveType::TimestampNtz => ArrowDataType::Timestamp(TimeUnit::Microsecond, None),
+            PrimitiveType::Decimal(precision, scale) => {
+                ArrowDataType::Decimal128(*precision, *scale)
+            }
+        },
+        DataType::Array(a) => ArrowDataType::List(Arc::new(Field::new(
+            "element",
+            arrow_data_type(a.element_type())?,
+            a.contains_null(),
+        ))),
+        DataType::Struct(s) => ArrowDataType::Struct(
+            s.fields()
+                .into_iter()
+                .map(arrow_field)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .collect::<Fields>(),
+        ),
+        DataType::Map(m) => {
+            let entries = Field::new(
+                "key_value",
+                ArrowDataType::Struct(Fields::from(vec![
+                    Field::new("key", arrow_data_type(m.key_type())?, false),
+                    Field::new(
+                        "value",
+                        arrow_data_type(m.value_type())?,
+                        m.value_contains_null(),
+                    ),
+                ])),
+                false,
+            );
+            ArrowDataType::Map(Arc::new(entries), false)
+        }
+    };
+    Ok(arrow_type)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_nested_schema() {
+        let schema_string = r#"{"type":"struct","fields":[
+            {"name":"id","type":"long","nullable":false,"metadata":{}},
+            {"name":"name","type":"string","nullable":true,"metadata":{}},
+            {"name":"address","type":{"type":"struct","fields":[
+                {"name":"city","type":"string","nullable":true,"metadata":{}},
+                {"name":"zip","type":"integer","nullable":true,"metadata":{}}
+            ]},"nullable":true,"metadata":{}},
+            {"name":"date","type":"date","nullable":true,"metadata":{}}
+        ]}"#;
+
+        let schema = arrow_schema_from_schema_string(schema_string).unwrap();
+
+        let expected = ArrowSchema::new(vec![
+            Field::new("id", ArrowDataType::Int64, false),
+            Field::new("name", ArrowDataType::Utf8, true),
+            Field::new(
+                "address",
+                ArrowDataType::Struct(Fields::from(vec![
+                    Field::new("city", ArrowDataType::Utf8, true),
+                    Field::new("zip", ArrowDataType::Int32, true),
+                ])),
+                true,
+            ),
+            Field::new("date", ArrowDataType::Date32, true),
+        ]);
+        assert_eq!(schema.as_ref(), &expected);
+    }
+
+    #[test]
+    fn reject_malformed_schema() {
+        let err = arrow_schema_from_schema_string("{}").unwrap_err();
+        assert_eq!(err.message(), "failed to parse table schema");
+    }
+}
@@ -0,0 +1,171 @@
+//! Circuit breaker failing requests fast while the server is unavailable.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use http::StatusCode;
+
+use crate::{DeltaSharingError, Result};
+
+/// Circuit breaker tracking consecutive retriable failures.
+///
+/// Once `failure_threshold` consecutive failures occur within `window`, the
+/// breaker opens and rejects requests until `cooldown` has passed. Any
+/// successful or non-retriable outcome resets the failure count.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    failure_threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    clock: Clock,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    first_failure: Option<Instant>,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(failure_threshold: u32, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            window,
+            cooldown,
+            clock: Clock::default(),
+            state: Mutex::new(CircuitState::default()),
+        }
+    }
+
+    /// Move the clock of the breaker forward, as if `duration` has passed.
+    #[cfg(test)]
+    pub(crate) fn advance(&self, duration: Duration) {
+        *self.clock.offset.lock().unwrap() += duration;
+    }
+
+    /// Check whether a request may be sent, failing fast while open.
+    pub(crate) fn check(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(open_until) if self.clock.now() < open_until => {
+                tracing::warn!("circuit breaker is open, failing fast");
+                Err(DeltaSharingError::server(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "CIRCUIT_OPEN",
+                    "circuit breaker is open after repeated failures",
+                ))
+            }
+            Some(_) => {
+                tracing::debug!("circuit breaker cooldown passed");
+                *state = CircuitState::default();
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Record the outcome of a request.
+    pub(crate) fn record(&self, failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        if !failed {
+            *state = CircuitState::default();
+            return;
+        }
+
+        let now = self.clock.now();
+        match state.first_failure {
+            Some(first) if now.duration_since(first) <= self.window => {
+                state.consecutive_failures += 1;
+            }
+            _ => {
+                state.first_failure = Some(now);
+                state.consecutive_failures = 1;
+            }
+        }
+
+        if state.consecutive_failures >= self.failure_threshold {
+            tracing::warn!(
+                failures = state.consecutive_failures,
+                "circuit breaker opened"
+            );
+            *state = CircuitState {
+                open_until: Some(now + self.cooldown),
+                ..CircuitState::default()
+            };
+        }
+    }
+}
+
+/// Source of the current time, which tests can move forward.
+#[derive(Debug, Default)]
+struct Clock {
+    #[cfg(test)]
+    offset: Mutex<Duration>,
+}
+
+impl Clock {
+    #[cfg(not(test))]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[cfg(test)]
+    fn now(&self) -> Instant {
+        Instant::now() + *self.offset.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn open_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(60));
+
+        breaker.record(true);
+        assert!(breaker.check().is_ok());
+        breaker.record(true);
+
+        let err = breaker.check().unwrap_err();
+        assert!(err.is_retriable());
+        assert_eq!(
+            err.message(),
+            "circuit breaker is open after repeated failures"
+        );
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(60));
+
+        breaker.record(true);
+        breaker.record(false);
+        breaker.record(true);
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn close_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60), Duration::from_secs(60));
+
+        breaker.record(true);
+        breaker.advance(Duration::from_secs(59));
+        assert!(breaker.check().is_err());
+
+        breaker.advance(Duration::from_secs(1));
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn reset_failures_outside_window() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(60));
+
+        breaker.record(true);
+        breaker.advance(Duration::from_secs(61));
+        breaker.record(true);
+        assert!(breaker.check().is_ok());
+    }
+}
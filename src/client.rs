@@ -1,51 +1,210 @@
-use std::str::FromStr;
+use std::fmt;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use http::{Method, StatusCode};
-use reqwest::{RequestBuilder, Response};
+use bytes::Bytes;
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use reqwest::{redirect, RequestBuilder, Response};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use url::Url;
 
-use crate::profile::TokenProvider;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::error::{is_retriable_status, ErrorKind};
+use crate::observer::{NoopObserver, Observer};
+use crate::profile::{DynTokenProvider, TokenProvider};
+use crate::request::body::TableDataQuery;
 use crate::request::pagination::{Pagination, PaginationExt};
+use crate::request::query::{TableChangesQuery, TableVersionQuery};
+use crate::request::{ResponseFormat, TableRef, UrlExt};
 use crate::response::{
-    ErrorResponse, GetShareResponse, ListSchemasResponse, ListSharesResponse, ListTablesResponse,
-    QueryTableChangesResponse, QueryTableDataResponse, QueryTableMetadataResponse,
-    QueryTableVersionResponse,
+    parse_action, parse_end_stream_action, parse_json, ErrorResponse, FileAction, GetShareResponse,
+    ListSchemasResponse, ListSharesResponse, ListTablesResponse, QueryTableChangesResponse,
+    QueryTableDataResponse, QueryTableMetadataResponse, QueryTableVersionResponse, TableAction,
+    TableMetadata,
 };
+use crate::signer::{NoopSigner, RequestSigner};
 use crate::DeltaSharingError;
 use crate::{
-    response::{Schema, Share, Table},
+    response::{Schema, SchemaNode, Share, ShareNode, Table},
     Profile, Result,
 };
 
+const CATALOG_TREE_CONCURRENCY: usize = 8;
+const MAX_DOWNLOAD_RESUMES: u32 = 3;
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+const DEFAULT_MAX_LINE_SIZE: usize = 16 * 1024 * 1024;
+const DEFAULT_MAX_LIST_RESULTS: usize = 1_000_000;
+const ERROR_CODE_ENDPOINT_NOT_FOUND: &str = "ENDPOINT_NOT_FOUND";
 const QUERY_PARAM_VERSION_TIMESTAMP: &str = "startingTimestamp";
+const HEADER_TABLE_VERSION: &str = "Delta-Table-Version";
+const HEADER_CAPABILITIES: &str = "delta-sharing-capabilities";
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Function rewriting the presigned URLs of file actions.
+pub type UrlRewrite = Arc<dyn Fn(Url) -> Url + Send + Sync>;
 
-#[derive(Debug)]
 pub struct DeltaSharingClient {
     client: reqwest::Client,
+    download_client: reqwest::Client,
     profile: Profile,
+    sort_results: bool,
+    sort_files: bool,
+    strict_parsing: bool,
+    get_share_fallback: bool,
+    validate_predicates: bool,
+    response_format: Option<ResponseFormat>,
+    default_page_size: Option<u32>,
+    max_list_results: usize,
+    resume_on_expiry: bool,
+    max_response_size: usize,
+    max_line_size: usize,
+    url_rewrite: Option<UrlRewrite>,
+    observer: Arc<dyn Observer>,
+    signer: Arc<dyn RequestSigner>,
+    default_headers: HeaderMap,
+    token_provider: Option<Arc<dyn DynTokenProvider>>,
+    circuit_breaker: Option<CircuitBreaker>,
+    capabilities: OnceLock<Capabilities>,
+}
+
+impl fmt::Debug for DeltaSharingClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeltaSharingClient")
+            .field("client", &self.client)
+            .field("download_client", &self.download_client)
+            .field("profile", &self.profile)
+            .field("sort_results", &self.sort_results)
+            .field("sort_files", &self.sort_files)
+            .field("strict_parsing", &self.strict_parsing)
+            .field("get_share_fallback", &self.get_share_fallback)
+            .field("validate_predicates", &self.validate_predicates)
+            .field("response_format", &self.response_format)
+            .field("default_page_size", &self.default_page_size)
+            .field("max_list_results", &self.max_list_results)
+            .field("resume_on_expiry", &self.resume_on_expiry)
+            .field("max_response_size", &self.max_response_size)
+            .field("max_line_size", &self.max_line_size)
+            .field("url_rewrite", &self.url_rewrite.is_some())
+            .field("observer", &self.observer)
+            .field("signer", &self.signer)
+            .field("default_headers", &self.default_headers.keys())
+            .field("token_provider", &self.token_provider.is_some())
+            .field("circuit_breaker", &self.circuit_breaker)
+            .field("capabilities", &self.capabilities.get())
+            .finish()
+    }
+}
+
+impl DeltaSharingClient {
+    /// Create a new client with default settings for the given profile.
+    pub fn new(profile: Profile) -> Result<Self> {
+        Self::builder(profile).build()
+    }
+
+    /// Create a builder to configure a new client for the given profile.
+    pub fn builder(profile: Profile) -> DeltaSharingClientBuilder {
+        DeltaSharingClientBuilder::new(profile)
+    }
+
+    /// Retrieve the underlying HTTP client used to download files, e.g. to
+    /// download presigned file URLs with the same connection pool, TLS and
+    /// redirect configuration.
+    ///
+    /// The HTTP client is shared, not copied: state shared through it, such as
+    /// pooled connections, affects this client too. Requests sent directly
+    /// through it are not authorized, signed or observed.
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.download_client
+    }
+
+    fn authorization_error(&self, e: DeltaSharingError) -> DeltaSharingError {
+        let message = self
+            .profile
+            .redact(&format!("failed to authorize request. Reason: {e}"));
+        tracing::error!(err = %message, "failed to authorize request");
+        DeltaSharingError::profile(message)
+    }
+
+    fn first_page(&self, page_size: Option<u32>) -> Pagination {
+        match page_size.or(self.default_page_size) {
+            Some(page_size) => Pagination::default().with_max_results(page_size),
+            None => Pagination::default(),
+        }
+    }
+
+    fn check_list_size(&self, collected: usize) -> Result<()> {
+        if collected > self.max_list_results {
+            tracing::error!(
+                collected,
+                limit = self.max_list_results,
+                "listing exceeded the maximum number of results"
+            );
+            return Err(DeltaSharingError::parse_response(format!(
+                "listing returned more than {} results",
+                self.max_list_results
+            )));
+        }
+        Ok(())
+    }
+
+    fn sorted<T: Ord>(&self, mut items: Vec<T>) -> Vec<T> {
+        if self.sort_results {
+            items.sort();
+        }
+        items
+    }
 }
 
 impl DeltaSharingClient {
     #[tracing::instrument]
     pub async fn list_shares(&self) -> Result<Vec<Share>> {
+        self.list_all_shares(None).await
+    }
+
+    /// Retrieve all shares, requesting pages of `page_size` shares instead of
+    /// the default page size.
+    #[tracing::instrument]
+    pub async fn list_shares_with_page_size(&self, page_size: u32) -> Result<Vec<Share>> {
+        self.list_all_shares(Some(page_size)).await
+    }
+
+    async fn list_all_shares(&self, page_size: Option<u32>) -> Result<Vec<Share>> {
         let mut shares = vec![];
-        let mut pagination = Pagination::default();
+        let mut pagination = self.first_page(page_size);
         while !pagination.is_finished() {
             let response = self.list_shares_raw(&pagination).await?;
-            pagination.set_page_token(response.next_page_token());
+            pagination.advance(response.next_page_token())?;
             shares.extend(response);
+            self.check_list_size(shares.len())?;
         }
-        Ok(shares)
+        Ok(self.sorted(shares))
     }
 
     #[tracing::instrument]
     pub async fn get_share(&self, share_name: &str) -> Result<Option<Share>> {
-        let res = self.get_share_raw(share_name).await;
+        let res = match self.request_share(share_name).await {
+            Ok(response) if response.status() == StatusCode::NOT_FOUND => {
+                Err(self.share_not_found_error(response, share_name).await)
+            }
+            Ok(response) => {
+                self.handle_response::<GetShareResponse>(response, &format!("share {share_name}"))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
         match res {
             Ok(r) => Ok(Some(r.share)),
+            Err(e) if is_unsupported_endpoint(&e) => {
+                if !self.get_share_fallback {
+                    return Err(e);
+                }
+                tracing::debug!(share_name, "get share unsupported, listing shares instead");
+                let shares = self.list_shares().await?;
+                Ok(shares.into_iter().find(|share| share.name() == share_name))
+            }
             Err(e) if e.is_not_found() => Ok(None),
             Err(e) => Err(e),
         }
@@ -53,26 +212,66 @@ impl DeltaSharingClient {
 
     #[tracing::instrument]
     pub async fn list_schemas(&self, share_name: &str) -> Result<Vec<Schema>> {
+        self.list_all_schemas(share_name, None).await
+    }
+
+    /// Retrieve all schemas of a share, requesting pages of `page_size`
+    /// schemas instead of the default page size.
+    #[tracing::instrument]
+    pub async fn list_schemas_with_page_size(
+        &self,
+        share_name: &str,
+        page_size: u32,
+    ) -> Result<Vec<Schema>> {
+        self.list_all_schemas(share_name, Some(page_size)).await
+    }
+
+    async fn list_all_schemas(
+        &self,
+        share_name: &str,
+        page_size: Option<u32>,
+    ) -> Result<Vec<Schema>> {
         let mut schemas = vec![];
-        let mut pagination = Pagination::default();
+        let mut pagination = self.first_page(page_size);
         while !pagination.is_finished() {
             let response = self.list_schemas_raw(share_name, &pagination).await?;
-            pagination.set_page_token(response.next_page_token());
+            pagination.advance(response.next_page_token())?;
             schemas.extend(response);
+            self.check_list_size(schemas.len())?;
         }
-        Ok(schemas)
+        Ok(self.sorted(schemas))
     }
 
     #[tracing::instrument]
     pub async fn list_tables_in_share(&self, share: &str) -> Result<Vec<Table>> {
+        self.list_all_tables_in_share(share, None).await
+    }
+
+    /// Retrieve all tables of a share, requesting pages of `page_size` tables
+    /// instead of the default page size.
+    #[tracing::instrument]
+    pub async fn list_tables_in_share_with_page_size(
+        &self,
+        share: &str,
+        page_size: u32,
+    ) -> Result<Vec<Table>> {
+        self.list_all_tables_in_share(share, Some(page_size)).await
+    }
+
+    async fn list_all_tables_in_share(
+        &self,
+        share: &str,
+        page_size: Option<u32>,
+    ) -> Result<Vec<Table>> {
         let mut tables = vec![];
-        let mut pagination = Pagination::default();
+        let mut pagination = self.first_page(page_size);
         while !pagination.is_finished() {
             let response = self.list_tables_in_share_raw(share, &pagination).await?;
-            pagination.set_page_token(response.next_page_token());
+            pagination.advance(response.next_page_token())?;
             tables.extend(response);
+            self.check_list_size(tables.len())?;
         }
-        Ok(tables)
+        Ok(self.sorted(tables))
     }
 
     #[tracing::instrument]
@@ -80,19 +279,83 @@ impl DeltaSharingClient {
         &self,
         share_name: &str,
         schema_name: &str,
+    ) -> Result<Vec<Table>> {
+        self.list_all_tables_in_schema(share_name, schema_name, None)
+            .await
+    }
+
+    /// Retrieve all tables of a schema, requesting pages of `page_size`
+    /// tables instead of the default page size.
+    #[tracing::instrument]
+    pub async fn list_tables_in_schema_with_page_size(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        page_size: u32,
+    ) -> Result<Vec<Table>> {
+        self.list_all_tables_in_schema(share_name, schema_name, Some(page_size))
+            .await
+    }
+
+    async fn list_all_tables_in_schema(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        page_size: Option<u32>,
     ) -> Result<Vec<Table>> {
         let mut tables = vec![];
-        let mut pagination = Pagination::default();
+        let mut pagination = self.first_page(page_size);
         while !pagination.is_finished() {
             let response = self
                 .list_tables_in_schema_raw(share_name, schema_name, &pagination)
                 .await?;
-            pagination.set_page_token(response.next_page_token());
+            pagination.advance(response.next_page_token())?;
             tables.extend(response);
+            self.check_list_size(tables.len())?;
         }
-        Ok(tables)
+        Ok(self.sorted(tables))
     }
 
+    /// Retrieve the full catalog of shares, schemas and tables as a tree.
+    ///
+    /// This lists all shares, then the schemas of every share, then the
+    /// tables of every schema, so it issues at least `1 + shares + schemas`
+    /// requests (more if the results span several pages). The schemas and
+    /// tables are listed concurrently, with at most 8 requests in flight.
+    #[tracing::instrument]
+    pub async fn catalog_tree(&self) -> Result<Vec<ShareNode>> {
+        let shares = self.list_shares().await?;
+        let schemas = stream::iter(&shares)
+            .map(|share| self.list_schemas(share.name()))
+            .buffered(CATALOG_TREE_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mut tables = stream::iter(schemas.iter().flatten())
+            .map(|schema| self.list_tables_in_schema(schema.share(), schema.name()))
+            .buffered(CATALOG_TREE_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?
+            .into_iter();
+
+        let tree = shares
+            .into_iter()
+            .zip(schemas)
+            .map(|(share, schemas)| ShareNode {
+                share,
+                schemas: schemas
+                    .into_iter()
+                    .zip(tables.by_ref())
+                    .map(|(schema, tables)| SchemaNode { schema, tables })
+                    .collect(),
+            })
+            .collect();
+        Ok(tree)
+    }
+
+    /// Retrieve the version of a table.
+    ///
+    /// A [`TableVersionQuery::Relative`] version is resolved against the
+    /// latest version, which is fetched first.
     #[tracing::instrument]
     pub async fn get_table_version(
         &self,
@@ -101,23 +364,37 @@ impl DeltaSharingClient {
         table_name: &str,
         version: &TableVersionQuery,
     ) -> Result<QueryTableVersionResponse> {
-        self.get_table_version_raw(
-            share_name,
-            schema_name,
-            table_name,
-            version.to_timestamp().as_deref(),
-        )
-        .await
+        let response = self
+            .get_table_version_raw(
+                share_name,
+                schema_name,
+                table_name,
+                version.to_timestamp().as_deref(),
+            )
+            .await?;
+        match version {
+            TableVersionQuery::Relative(_) => Ok(QueryTableVersionResponse::new(
+                version.resolve(response.version()),
+            )),
+            TableVersionQuery::Latest | TableVersionQuery::Timestamp(_) => Ok(response),
+        }
     }
 
+    /// Retrieve the metadata of a table, with its schema, partition columns
+    /// and table properties.
+    ///
+    /// Use [`DeltaSharingClient::get_table_metadata_raw`] to access the
+    /// protocol and metadata actions as returned by the server.
     #[tracing::instrument]
     pub async fn get_table_metadata(
         &self,
         share_name: &str,
         schema_name: &str,
         table_name: &str,
-    ) -> Result<QueryTableMetadataResponse> {
-        todo!()
+    ) -> Result<TableMetadata> {
+        self.get_table_metadata_raw(share_name, schema_name, table_name)
+            .await?
+            .try_into()
     }
 
     #[tracing::instrument]
@@ -126,8 +403,15 @@ impl DeltaSharingClient {
         share_name: &str,
         schema_name: &str,
         table_name: &str,
+        query: &TableDataQuery,
     ) -> Result<QueryTableDataResponse> {
-        todo!()
+        let mut data = self
+            .get_table_data_raw(share_name, schema_name, table_name, query)
+            .await?;
+        if self.sort_files {
+            data.sort_files();
+        }
+        Ok(data)
     }
 
     #[tracing::instrument]
@@ -136,8 +420,155 @@ impl DeltaSharingClient {
         share_name: &str,
         schema_name: &str,
         table_name: &str,
+        query: &TableChangesQuery,
     ) -> Result<QueryTableChangesResponse> {
-        todo!()
+        self.get_table_changes_raw(share_name, schema_name, table_name, query)
+            .await
+    }
+
+    /// Check that the server is reachable and accepts the profile.
+    ///
+    /// Issues a minimal authenticated request (listing at most one share) and
+    /// returns its round-trip latency. Use [`DeltaSharingError::is_retriable`]
+    /// to classify a failed ping.
+    #[tracing::instrument]
+    pub async fn ping(&self) -> Result<Duration> {
+        let start = Instant::now();
+        self.list_shares_raw(&Pagination::from_start(Some(1)))
+            .await?;
+        let latency = start.elapsed();
+        tracing::debug!(latency = ?latency, "ping succeeded");
+        Ok(latency)
+    }
+
+    /// Retrieve the capabilities advertised by the server.
+    ///
+    /// The capabilities are read from the `delta-sharing-capabilities` header
+    /// of a minimal request (listing at most one share) and cached, so only
+    /// the first call reaches the server.
+    #[tracing::instrument]
+    pub async fn server_capabilities(&self) -> Result<Capabilities> {
+        if let Some(capabilities) = self.capabilities.get() {
+            return Ok(capabilities.clone());
+        }
+
+        let url = self
+            .profile
+            .endpoint()
+            .join("/shares")?
+            .with_pagination(&Pagination::from_start(Some(1)));
+
+        let request = self
+            .client
+            .request(Method::GET, url)
+            .authorize(&self.profile, self.token_provider.as_deref())
+            .await
+            .map_err(|e| self.authorization_error(e))?;
+
+        let response = self.send(request).await?;
+        if response.status() != StatusCode::OK {
+            return Err(self.handle_error_response(response, "shares").await);
+        }
+        let header = response
+            .headers()
+            .get(HEADER_CAPABILITIES)
+            .and_then(|v| v.to_str().ok());
+        let capabilities = Capabilities::from_header(header);
+        tracing::debug!(capabilities = ?capabilities, "server capabilities detected");
+
+        Ok(self.capabilities.get_or_init(|| capabilities).clone())
+    }
+
+    /// Retrieve the latest version of a table whose change data feed can be
+    /// queried.
+    ///
+    /// The protocol does not expose the earliest version the server retains
+    /// changes for. A changes query starting before that version is rejected
+    /// by the server.
+    #[tracing::instrument]
+    pub async fn latest_cdf_version(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+    ) -> Result<u64> {
+        let metadata = self
+            .get_table_metadata(share_name, schema_name, table_name)
+            .await?;
+        if !metadata.change_data_feed_enabled() {
+            tracing::error!("change data feed is not enabled on table");
+            return Err(DeltaSharingError::request(format!(
+                "change data feed is not enabled on table {share_name}.{schema_name}.{table_name}"
+            )));
+        }
+
+        let latest = self
+            .get_table_version(
+                share_name,
+                schema_name,
+                table_name,
+                &TableVersionQuery::Latest,
+            )
+            .await?;
+        Ok(latest.version())
+    }
+
+    /// Retrieve the version of a table returned by one of the list methods.
+    #[tracing::instrument]
+    pub async fn get_table_version_for(
+        &self,
+        table: &Table,
+        version: &TableVersionQuery,
+    ) -> Result<QueryTableVersionResponse> {
+        self.get_table_version(table.share(), table.schema(), table.name(), version)
+            .await
+    }
+
+    /// Retrieve the latest version of many tables, running at most
+    /// `concurrency` requests at a time.
+    ///
+    /// A failure for one table does not abort the others; every table is
+    /// returned with its own result, in the order of `tables`.
+    #[tracing::instrument(skip(tables), fields(tables = tables.len()))]
+    pub async fn get_table_versions(
+        &self,
+        tables: &[TableRef],
+        concurrency: usize,
+    ) -> Vec<(TableRef, Result<u64>)> {
+        stream::iter(tables)
+            .map(|table| async move {
+                let version = self
+                    .get_table_version(
+                        table.share(),
+                        table.schema(),
+                        table.name(),
+                        &TableVersionQuery::Latest,
+                    )
+                    .await
+                    .map(|response| response.version());
+                (table.clone(), version)
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Retrieve the metadata of a table returned by one of the list methods.
+    #[tracing::instrument]
+    pub async fn get_table_metadata_for(&self, table: &Table) -> Result<TableMetadata> {
+        self.get_table_metadata(table.share(), table.schema(), table.name())
+            .await
+    }
+
+    /// Retrieve the data of a table returned by one of the list methods.
+    #[tracing::instrument]
+    pub async fn get_table_data_for(
+        &self,
+        table: &Table,
+        query: &TableDataQuery,
+    ) -> Result<QueryTableDataResponse> {
+        self.get_table_data(table.share(), table.schema(), table.name(), query)
+            .await
     }
 }
 
@@ -147,58 +578,80 @@ impl DeltaSharingClient {
         let url = self
             .profile
             .endpoint()
-            .join("/shares")
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to construct URL");
-                DeltaSharingError::internal("failed to construct URL")
-            })?
+            .join("/shares")?
             .with_pagination(pagination);
         tracing::debug!(url = %url, "endpoint URL constructed");
 
         let request = self
             .client
             .request(Method::GET, url)
-            .authorize(&self.profile)
+            .authorize(&self.profile, self.token_provider.as_deref())
             .await
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to authorize request");
-                DeltaSharingError::profile("failed to authorize request")
-            })?;
+            .map_err(|e| self.authorization_error(e))?;
         tracing::debug!("prepared request");
 
-        let response = request.send().await?;
+        let response = self.send(request).await?;
         tracing::debug!("received response");
 
-        handle_response(response).await
+        self.handle_response(response, "shares").await
     }
 
     #[tracing::instrument]
     pub async fn get_share_raw(&self, share_name: &str) -> Result<GetShareResponse> {
+        let response = self.request_share(share_name).await?;
+        self.handle_response(response, &format!("share {share_name}"))
+            .await
+    }
+
+    async fn request_share(&self, share_name: &str) -> Result<Response> {
         let url = self
             .profile
             .endpoint()
-            .join(&format!("/shares/{share_name}"))
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to construct URL");
-                DeltaSharingError::internal("failed to construct URL")
-            })?;
+            .join(&format!("/shares/{share_name}"))?;
         tracing::debug!(url = %url, "endpoint URL constructed");
 
         let request = self
             .client
             .request(Method::GET, url)
-            .authorize(&self.profile)
+            .authorize(&self.profile, self.token_provider.as_deref())
             .await
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to authorize request");
-                DeltaSharingError::profile("failed to authorize request")
-            })?;
+            .map_err(|e| self.authorization_error(e))?;
         tracing::debug!("prepared request");
 
-        let response = request.send().await?;
+        let response = self.send(request).await?;
         tracing::debug!("received response");
+        Ok(response)
+    }
 
-        handle_response(response).await
+    /// Convert a 404 (not found) response of a `get_share` request into an
+    /// error. A body that is not an error response means that the server
+    /// does not implement the endpoint, rather than that the share does not
+    /// exist.
+    async fn share_not_found_error(
+        &self,
+        response: Response,
+        share_name: &str,
+    ) -> DeltaSharingError {
+        let status_code = response.status();
+        let body = match self.read_body(response).await {
+            Ok(body) => body,
+            Err(e) => return e,
+        };
+        match serde_json::from_slice::<ErrorResponse>(&body) {
+            Ok(err) => {
+                tracing::debug!(share_name, "response parsed");
+                let message = format!("{} (share {share_name})", err.message());
+                DeltaSharingError::client(status_code, err.error_code(), message)
+            }
+            Err(_) => {
+                tracing::error!(share_name, "endpoint not found");
+                DeltaSharingError::client(
+                    status_code,
+                    ERROR_CODE_ENDPOINT_NOT_FOUND,
+                    format!("endpoint not found (share {share_name})"),
+                )
+            }
+        }
     }
 
     #[tracing::instrument]
@@ -210,25 +663,19 @@ impl DeltaSharingClient {
         let url = self
             .profile
             .endpoint()
-            .join(&format!("/shares/{share_name}/schemas"))
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to construct URL");
-                DeltaSharingError::internal("failed to construct URL")
-            })?
+            .join(&format!("/shares/{share_name}/schemas"))?
             .with_pagination(pagination);
 
         let request = self
             .client
             .request(Method::GET, url)
-            .authorize(&self.profile)
+            .authorize(&self.profile, self.token_provider.as_deref())
             .await
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to authorize request");
-                DeltaSharingError::profile("failed to authorize request")
-            })?;
+            .map_err(|e| self.authorization_error(e))?;
 
-        let response = request.send().await?;
-        handle_response(response).await
+        let response = self.send(request).await?;
+        self.handle_response(response, &format!("share {share_name}"))
+            .await
     }
 
     #[tracing::instrument]
@@ -240,25 +687,19 @@ impl DeltaSharingClient {
         let url = self
             .profile
             .endpoint()
-            .join(&format!("/shares/{share_name}/schemas/all-tables"))
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to construct URL");
-                DeltaSharingError::internal("failed to construct URL")
-            })?
+            .join(&format!("/shares/{share_name}/schemas/all-tables"))?
             .with_pagination(pagination);
 
         let request = self
             .client
             .request(Method::GET, url)
-            .authorize(&self.profile)
+            .authorize(&self.profile, self.token_provider.as_deref())
             .await
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to authorize request");
-                DeltaSharingError::profile("failed to authorize request")
-            })?;
+            .map_err(|e| self.authorization_error(e))?;
 
-        let response = request.send().await?;
-        handle_response(response).await
+        let response = self.send(request).await?;
+        self.handle_response(response, &format!("share {share_name}"))
+            .await
     }
 
     #[tracing::instrument]
@@ -273,25 +714,19 @@ impl DeltaSharingClient {
             .endpoint()
             .join(&format!(
                 "/shares/{share_name}/schemas/{schema_name}/tables"
-            ))
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to construct URL");
-                DeltaSharingError::internal("failed to construct URL")
-            })?
+            ))?
             .with_pagination(pagination);
 
         let request = self
             .client
             .request(Method::GET, url)
-            .authorize(&self.profile)
+            .authorize(&self.profile, self.token_provider.as_deref())
             .await
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to authorize request");
-                DeltaSharingError::profile("failed to authorize request")
-            })?;
+            .map_err(|e| self.authorization_error(e))?;
 
-        let response = request.send().await?;
-        handle_response(response).await
+        let response = self.send(request).await?;
+        self.handle_response(response, &format!("schema {share_name}.{schema_name}"))
+            .await
     }
 
     #[tracing::instrument]
@@ -302,16 +737,9 @@ impl DeltaSharingClient {
         table_name: &str,
         starting_timestamp: Option<&str>,
     ) -> Result<QueryTableVersionResponse> {
-        let mut endpoint = self
-            .profile
-            .prefix()
-            .join(&format!(
-                "/shares/{share_name}/schemas/{schema_name}/tables/{table_name}/version"
-            ))
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to construct URL");
-                DeltaSharingError::internal("failed to construct endpoint URL")
-            })?;
+        let mut endpoint = self.profile.prefix().join(&format!(
+            "/shares/{share_name}/schemas/{schema_name}/tables/{table_name}/version"
+        ))?;
         if let Some(ts) = starting_timestamp {
             endpoint
                 .query_pairs_mut()
@@ -319,42 +747,11 @@ impl DeltaSharingClient {
         }
         tracing::debug!(endpoint = %endpoint, "URL constructed");
 
-        let request = self
-            .client
-            .get(endpoint)
-            .authorize(&self.profile)
-            .await
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to authorize request");
-                DeltaSharingError::profile(format!("failed to authorize request. Reason: {e}"))
-            })?;
-        tracing::debug!("prepared request");
-
-        let response = request.send().await?;
-        let status = response.status();
-        tracing::debug!(status_code = %status, "server responded");
-
-        match status {
-            StatusCode::OK => response
-                .headers()
-                .get("Delta-Table-Version")
-                .and_then(|v| v.to_str().ok())
-                .and_then(|v| v.parse::<u64>().ok())
-                .ok_or(DeltaSharingError::parse_response(
-                    "parsing delta-table-version header failed",
-                )),
-            StatusCode::BAD_REQUEST
-            | StatusCode::UNAUTHORIZED
-            | StatusCode::FORBIDDEN
-            | StatusCode::NOT_FOUND => Err(DeltaSharingError::internal("ugh")),
-            StatusCode::INTERNAL_SERVER_ERROR => Err(DeltaSharingError::internal("ugh")),
-            _ => {
-                tracing::error!(status_code = %status, "unexpected server status code");
-                Err(DeltaSharingError::internal("unknown server response"))
-            }
-        };
-
-        todo!()
+        let request = self.client.get(endpoint);
+        let response = self
+            .send_table_query(request, share_name, schema_name, table_name)
+            .await?;
+        table_version_header(&response).map(QueryTableVersionResponse::new)
     }
 
     #[tracing::instrument]
@@ -364,171 +761,2956 @@ impl DeltaSharingClient {
         schema_name: &str,
         table_name: &str,
     ) -> Result<QueryTableMetadataResponse> {
-        let endpoint = self
-            .profile
-            .prefix()
-            .join(&format!(
-                "/shares/{share_name}/schemas/{schema_name}/tables/{table_name}/metadata"
-            ))
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to construct URL");
-                DeltaSharingError::internal("failed to construct endpoint URL")
-            })?;
+        let endpoint = self.profile.prefix().join(&format!(
+            "/shares/{share_name}/schemas/{schema_name}/tables/{table_name}/metadata"
+        ))?;
+        self.check_response_format().await?;
 
-        let request = self
-            .client
-            .get(endpoint)
-            .authorize(&self.profile)
-            .await
-            .map_err(|e| {
-                tracing::error!(err = ?e, "failed to authorize request");
-                DeltaSharingError::profile(format!("failed to authorize request. Reason: {e}"))
-            })?;
+        let request = self.client.get(endpoint);
+        let response = self
+            .send_table_query(request, share_name, schema_name, table_name)
+            .await?;
+        let version = table_version_header(&response)?;
+        let body = self.read_ndjson_body(response).await?;
+        QueryTableMetadataResponse::from_ndjson(version, &body, self.strict_parsing)
+    }
 
-        let response = request.send().await?;
-        let status = response.status();
-        tracing::debug!(status_code = %status, "server responded");
+    /// Retrieve the data of a table, following the page tokens of paginated
+    /// data queries and merging the files of all pages.
+    #[tracing::instrument]
+    pub async fn get_table_data_raw(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+        query: &TableDataQuery,
+    ) -> Result<QueryTableDataResponse> {
+        self.check_predicate_columns(share_name, schema_name, table_name, query)
+            .await?;
+        let mut data: Option<QueryTableDataResponse> = None;
+        let mut pagination = Pagination::default();
+        while !pagination.is_finished() {
+            let page_query = query.clone().with_page_token(pagination.page_token());
+            let page = self
+                .get_table_data_page(share_name, schema_name, table_name, &page_query)
+                .await?;
+            pagination.advance(page.next_page_token())?;
+            match &mut data {
+                Some(data) => data.extend_page(page),
+                None => data = Some(page),
+            }
+        }
+        data.ok_or_else(|| DeltaSharingError::internal("no data query page was requested"))
+    }
 
-        todo!()
+    /// Validate the predicate columns of a data query against the table
+    /// schema, if enabled.
+    async fn check_predicate_columns(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+        query: &TableDataQuery,
+    ) -> Result<()> {
+        if !self.validate_predicates || !query.has_predicate_hints() {
+            return Ok(());
+        }
+        let metadata = self
+            .get_table_metadata(share_name, schema_name, table_name)
+            .await?;
+        query.validate_predicate_columns(&metadata)
     }
 
     #[tracing::instrument]
-    pub async fn get_table_data_raw(
+    async fn get_table_data_page(
         &self,
         share_name: &str,
         schema_name: &str,
         table_name: &str,
+        query: &TableDataQuery,
     ) -> Result<QueryTableDataResponse> {
-        todo!()
+        let response = self
+            .send_data_query(share_name, schema_name, table_name, query)
+            .await?;
+        let version = table_version_header(&response)?;
+        let body = self.read_ndjson_body(response).await?;
+        let mut data = QueryTableDataResponse::from_ndjson(version, &body, self.strict_parsing)?;
+        if let Some(rewrite) = &self.url_rewrite {
+            data.rewrite_file_urls(rewrite.as_ref());
+        }
+        Ok(data)
     }
 
+    /// Retrieve the changes of a table, following the page tokens of
+    /// paginated changes queries and merging the files of all pages.
     #[tracing::instrument]
     pub async fn get_table_changes_raw(
         &self,
         share_name: &str,
         schema_name: &str,
         table_name: &str,
+        query: &TableChangesQuery,
     ) -> Result<QueryTableChangesResponse> {
-        todo!()
+        let mut changes: Option<QueryTableChangesResponse> = None;
+        let mut pagination = Pagination::default();
+        while !pagination.is_finished() {
+            let page_query = query.clone().with_page_token(pagination.page_token());
+            let page = self
+                .get_table_changes_page(share_name, schema_name, table_name, &page_query)
+                .await?;
+            pagination.advance(page.next_page_token())?;
+            match &mut changes {
+                Some(changes) => changes.extend_page(page),
+                None => changes = Some(page),
+            }
+        }
+        changes.ok_or_else(|| DeltaSharingError::internal("no changes query page was requested"))
     }
-}
 
-#[derive(Debug, Serialize)]
-pub struct TableDataQuery {
-    predicate_hints: Option<String>,
-    json_predicate_hints: Option<String>,
-    limit_hint: Option<u32>,
-    version: Option<u32>,
-    timestamp: Option<String>,
-    starting_version: Option<u32>,
-    ending_version: Option<u32>,
-}
+    /// Retrieve the changes of a table as a stream of actions, requesting the
+    /// pages of a paginated changes query as the stream is polled.
+    ///
+    /// The stream yields the protocol and metadata actions once, followed by
+    /// the file actions of all pages. Only a single page is buffered at a
+    /// time; limit its size with [`TableChangesQuery::with_max_files`].
+    pub fn get_table_changes_stream<'a>(
+        &'a self,
+        share_name: &'a str,
+        schema_name: &'a str,
+        table_name: &'a str,
+        query: &'a TableChangesQuery,
+    ) -> impl Stream<Item = Result<TableAction>> + 'a {
+        let pages = stream::try_unfold(
+            (Pagination::default(), true),
+            move |(mut pagination, is_first)| async move {
+                if pagination.is_finished() {
+                    return Ok(None);
+                }
+                let page_query = query.clone().with_page_token(pagination.page_token());
+                let page = self
+                    .get_table_changes_page(share_name, schema_name, table_name, &page_query)
+                    .await?;
+                pagination.advance(page.next_page_token())?;
 
-#[derive(Debug, Serialize)]
-pub struct TableChangesQuery {
-    starting_version: Option<u32>,
-    ending_version: Option<u32>,
-    starting_timestamp: Option<String>,
-    ending_timestamp: Option<String>,
-    include_historical_metadata: Option<bool>,
-}
+                let (protocol, metadata, files) = page.into_actions();
+                let header = is_first.then_some([protocol, metadata]);
+                let actions = header.into_iter().flatten().chain(files).map(Ok);
+                Ok(Some((stream::iter(actions), (pagination, false))))
+            },
+        );
+        pages.try_flatten()
+    }
 
-#[derive(Debug)]
-pub enum TableVersionQuery {
-    Latest,
-    Timestamp(DateTime<Utc>),
-}
+    #[tracing::instrument]
+    async fn get_table_changes_page(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+        query: &TableChangesQuery,
+    ) -> Result<QueryTableChangesResponse> {
+        let endpoint = self
+            .profile
+            .prefix()
+            .join(&format!(
+                "/shares/{share_name}/schemas/{schema_name}/tables/{table_name}/changes"
+            ))?
+            .with_query_params(query);
+        tracing::debug!(endpoint = %endpoint, "URL constructed");
+        self.check_response_format().await?;
 
-impl TableVersionQuery {
-    fn to_timestamp(&self) -> Option<String> {
-        match self {
-            TableVersionQuery::Latest => None,
-            TableVersionQuery::Timestamp(ts) => Some(ts.to_rfc3339()),
+        let request = self.client.get(endpoint);
+        let response = self
+            .send_table_query(request, share_name, schema_name, table_name)
+            .await?;
+        let version = table_version_header(&response)?;
+        let body = self.read_ndjson_body(response).await?;
+        let mut changes =
+            QueryTableChangesResponse::from_ndjson(version, &body, self.strict_parsing)?;
+        if let Some(rewrite) = &self.url_rewrite {
+            changes.rewrite_file_urls(rewrite.as_ref());
         }
+        Ok(changes)
     }
-}
 
-impl Default for TableVersionQuery {
-    fn default() -> Self {
-        TableVersionQuery::Latest
+    /// Fail with a clear error before a metadata, data or changes query in the
+    /// delta response format if the server does not support that format. The
+    /// server capabilities are detected by the first such query and cached.
+    async fn check_response_format(&self) -> Result<()> {
+        if self.response_format != Some(ResponseFormat::Delta) {
+            return Ok(());
+        }
+        self.server_capabilities()
+            .await?
+            .require_response_format("delta")
     }
-}
 
-impl FromStr for TableVersionQuery {
-    type Err = DeltaSharingError;
+    /// Send an authorized query to an endpoint of a table, returning the
+    /// response if the server responded with 200 (OK).
+    async fn send_table_query(
+        &self,
+        request: RequestBuilder,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+    ) -> Result<Response> {
+        let request = request
+            .authorize(&self.profile, self.token_provider.as_deref())
+            .await
+            .map_err(|e| self.authorization_error(e))?;
+
+        let response = self.send(request).await?;
+        let status = response.status();
+        tracing::debug!(status_code = %status, "server responded");
 
-    fn from_str(s: &str) -> Result<Self> {
-        if s == "latest" {
-            Ok(TableVersionQuery::Latest)
-        } else {
-            let ts = DateTime::parse_from_rfc3339(s).map_err(|e| {
-                DeltaSharingError::internal(format!("failed to parse timestamp: {}", e))
-            })?;
-            Ok(TableVersionQuery::Timestamp(ts.into()))
+        match status {
+            StatusCode::OK => Ok(response),
+            _ => Err(self
+                .handle_error_response(
+                    response,
+                    &format!("table {share_name}.{schema_name}.{table_name}"),
+                )
+                .await),
         }
     }
-}
 
-async fn handle_response<T: DeserializeOwned>(response: Response) -> Result<T> {
-    let status_code = response.status();
-    tracing::debug!(status_code = %status_code, "server responded");
-
-    match status_code {
-        StatusCode::OK => {
-            let res = response.json::<T>().await.map_err(|e| {
-                tracing::error!(err = ?e, "failed to parse server response");
-                DeltaSharingError::parse_response("failed to parse server response")
-            })?;
-            tracing::debug!("response parsed");
-            Ok(res)
-        }
-
-        StatusCode::BAD_REQUEST
-        | StatusCode::UNAUTHORIZED
-        | StatusCode::FORBIDDEN
-        | StatusCode::NOT_FOUND => {
-            let err = response.json::<ErrorResponse>().await.map_err(|e| {
-                tracing::error!(err = ?e, "failed to parse server response");
-                DeltaSharingError::parse_response("failed to parse server response")
-            })?;
-            tracing::debug!("response parsed");
-            Err(DeltaSharingError::client(status_code, err.error_code(), err.message()).into())
-        }
-        StatusCode::INTERNAL_SERVER_ERROR => {
-            let err = response.json::<ErrorResponse>().await.map_err(|e| {
-                tracing::error!(err = ?e, "failed to parse server response");
-                DeltaSharingError::parse_response("failed to parse server response")
-            })?;
-            tracing::debug!("response parsed");
-            Err(DeltaSharingError::server(status_code, err.error_code(), err.message()).into())
-        }
-        _ => {
-            tracing::warn!(status_code = %status_code, "unexpected HTTP status");
-            Err(DeltaSharingError::internal("unknown server response"))
-        }
+    /// Send a data query of a table, returning the response if the server
+    /// responded with 200 (OK).
+    async fn send_data_query(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+        query: &TableDataQuery,
+    ) -> Result<Response> {
+        query.validate()?;
+        let endpoint = self.profile.prefix().join(&format!(
+            "/shares/{share_name}/schemas/{schema_name}/tables/{table_name}/query"
+        ))?;
+        self.check_response_format().await?;
+
+        let request = self.client.post(endpoint).json(query);
+        self.send_table_query(request, share_name, schema_name, table_name)
+            .await
     }
 }
 
-#[async_trait]
-trait AuthorizationExt
-where
-    Self: Sized,
-{
-    async fn authorize(self, profile: &Profile) -> Result<Self>;
-}
+impl DeltaSharingClient {
+    /// Retrieve the data of a table as a stream of file actions.
+    ///
+    /// Unlike [`get_table_data`](Self::get_table_data), which buffers the
+    /// full response body, the file actions are parsed and yielded one line
+    /// at a time as they arrive from the server. Memory usage is bounded by
+    /// the maximum line size (see
+    /// [`with_max_line_size`](DeltaSharingClientBuilder::with_max_line_size))
+    /// plus a single network chunk, independent of the number of files. The
+    /// response body is only read while the stream is polled. For paginated
+    /// queries (see [`TableDataQuery::with_max_files`]) the next page is
+    /// requested once the previous one has been streamed.
+    #[tracing::instrument]
+    pub async fn get_table_data_stream<'a>(
+        &'a self,
+        share_name: &'a str,
+        schema_name: &'a str,
+        table_name: &'a str,
+        query: &'a TableDataQuery,
+    ) -> Result<TableDataStream<'a>> {
+        self.check_predicate_columns(share_name, schema_name, table_name, query)
+            .await?;
+        let response = self
+            .send_data_query(share_name, schema_name, table_name, query)
+            .await?;
+        let version = table_version_header(&response)?;
+        let lines = NdjsonLines::new(response, self.max_line_size, self.observer.clone());
+        let actions = stream::try_unfold(
+            (lines, Pagination::default()),
+            move |(mut lines, mut pagination)| async move {
+                loop {
+                    let Some(line) = lines.next_line().await? else {
+                        return Ok(None);
+                    };
+                    if let Some(end_stream) = parse_end_stream_action(&line) {
+                        pagination.advance(end_stream.into_next_page_token())?;
+                        if pagination.is_finished() {
+                            return Ok(None);
+                        }
+                        let page_query = query.clone().with_page_token(pagination.page_token());
+                        let response = self
+                            .send_data_query(share_name, schema_name, table_name, &page_query)
+                            .await?;
+                        lines =
+                            NdjsonLines::new(response, self.max_line_size, self.observer.clone());
+                        continue;
+                    }
 
-#[async_trait]
-impl AuthorizationExt for RequestBuilder {
-    async fn authorize(self, profile: &Profile) -> Result<Self> {
-        let token = profile.provide_token().await?;
-        let auth_req = self.bearer_auth(token);
-        Ok(auth_req)
+                    let mut action = parse_action(&line, self.strict_parsing)?;
+                    // Every page repeats the protocol and metadata actions,
+                    // which are only yielded for the first page.
+                    let is_first_page = pagination.page_token().is_none();
+                    if !is_first_page && (action.is_protocol() || action.is_metadata()) {
+                        continue;
+                    }
+                    if let Some(rewrite) = &self.url_rewrite {
+                        action.rewrite_url(rewrite.as_ref());
+                    }
+                    return Ok(Some((action, (lines, pagination))));
+                }
+            },
+        );
+        TableDataStream::from_actions(version, actions.boxed()).await
+    }
+
+    /// Retrieve the unparsed newline delimited JSON body of a data query.
+    ///
+    /// This is meant for debugging protocol issues, e.g. to attach the exact
+    /// server response to a bug report. The body is returned as sent by the
+    /// server: it is not validated, strict parsing and URL rewrites are not
+    /// applied, and the `Delta-Table-Version` header is ignored. Only the
+    /// maximum line size is enforced. Use
+    /// [`get_table_data`](Self::get_table_data) for the parsed response.
+    #[tracing::instrument]
+    pub async fn get_table_data_raw_bytes(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+        query: &TableDataQuery,
+    ) -> Result<Bytes> {
+        self.check_predicate_columns(share_name, schema_name, table_name, query)
+            .await?;
+        let response = self
+            .send_data_query(share_name, schema_name, table_name, query)
+            .await?;
+        Ok(Bytes::from(self.read_ndjson_bytes(response).await?))
     }
 }
 
-// TODO
-impl From<reqwest::Error> for DeltaSharingError {
+impl DeltaSharingClient {
+    /// Download the contents of a file returned by a data query as a stream
+    /// of chunks.
+    ///
+    /// The file is downloaded from its presigned URL, without authorization,
+    /// and the chunks are yielded as they arrive from the storage, so the
+    /// file is never buffered in memory as a whole. With
+    /// [`DeltaSharingClientBuilder::with_resume_on_expiry`] enabled, a
+    /// download that breaks off is resumed with a `Range` request for the
+    /// remaining bytes. If the presigned URL is rejected with 403 (forbidden)
+    /// because it expired, the table is queried again with the same `query`
+    /// to obtain a fresh URL for the file. A download is resumed at most 3
+    /// times. Dropping the stream cancels the download.
+    pub fn download_file<'a>(
+        &'a self,
+        share_name: &'a str,
+        schema_name: &'a str,
+        table_name: &'a str,
+        query: &'a TableDataQuery,
+        file: &'a dyn FileAction,
+    ) -> impl Stream<Item = Result<Bytes>> + 'a {
+        let download = FileDownload {
+            url: file.url().to_owned(),
+            response: None,
+            received: 0,
+            skip: 0,
+            resumes: 0,
+        };
+        stream::try_unfold(download, move |mut download| async move {
+            loop {
+                let failure = match self.next_download_chunk(&mut download).await {
+                    Ok(Some(chunk)) => return Ok(Some((chunk, download))),
+                    Ok(None) => return Ok(None),
+                    Err(failure) => failure,
+                };
+                let (expired, err) = match failure {
+                    DownloadFailure::Expired(err) => (true, err),
+                    DownloadFailure::Interrupted(err) => (false, err),
+                    DownloadFailure::Failed(err) => return Err(err),
+                };
+                if !self.resume_on_expiry || download.resumes >= MAX_DOWNLOAD_RESUMES {
+                    return Err(err);
+                }
+
+                download.resumes += 1;
+                tracing::debug!(
+                    file_id = file.id(),
+                    received = download.received,
+                    expired,
+                    "resuming file download"
+                );
+                if expired {
+                    download.url = self
+                        .refresh_file_url(share_name, schema_name, table_name, query, file.id())
+                        .await?;
+                }
+            }
+        })
+    }
+
+    /// Download a byte range of a file returned by a data query, e.g. the
+    /// footer of a Parquet file.
+    ///
+    /// The range is requested from the presigned URL of the file with an
+    /// HTTP `Range` request, without authorization. Fails if the storage
+    /// ignores the range and responds with the full file instead of `206
+    /// Partial Content`. A range extending past the end of the file yields
+    /// the bytes up to the end of the file.
+    #[tracing::instrument]
+    pub async fn download_file_range(
+        &self,
+        file: &dyn FileAction,
+        range: Range<u64>,
+    ) -> Result<Bytes> {
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+        let mut response = self
+            .download_client
+            .get(file.url())
+            .header(
+                http::header::RANGE,
+                format!("bytes={}-{}", range.start, range.end - 1),
+            )
+            .send()
+            .await
+            .map_err(download_transport_error)?;
+        let status = response.status();
+        tracing::debug!(status_code = %status, "storage responded");
+
+        match status {
+            StatusCode::PARTIAL_CONTENT => {}
+            StatusCode::OK => {
+                tracing::error!(file_id = file.id(), "storage ignored range request");
+                return Err(DeltaSharingError::download(
+                    Some(status),
+                    "storage ignored the range request and sent the full file",
+                ));
+            }
+            _ => return Err(download_error(status)),
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(download_transport_error)? {
+            self.observer.on_bytes_downloaded(chunk.len());
+            body.extend_from_slice(&chunk);
+        }
+        if body.len() as u64 > range.end - range.start {
+            tracing::error!(
+                file_id = file.id(),
+                received = body.len(),
+                "storage sent too many bytes"
+            );
+            return Err(DeltaSharingError::download(
+                Some(status),
+                "storage sent more bytes than requested",
+            ));
+        }
+        Ok(Bytes::from(body))
+    }
+
+    /// Receive the next chunk of a file download, requesting the bytes not
+    /// received yet if no download is in progress.
+    async fn next_download_chunk(
+        &self,
+        download: &mut FileDownload,
+    ) -> std::result::Result<Option<Bytes>, DownloadFailure> {
+        loop {
+            let mut response = match download.response.take() {
+                Some(response) => response,
+                None => self.request_download(download).await?,
+            };
+            let Some(mut chunk) = response
+                .chunk()
+                .await
+                .map_err(|e| DownloadFailure::Interrupted(download_transport_error(e)))?
+            else {
+                return Ok(None);
+            };
+            download.response = Some(response);
+            self.observer.on_bytes_downloaded(chunk.len());
+
+            let skip = download.skip.min(chunk.len() as u64);
+            download.skip -= skip;
+            let chunk = chunk.split_off(skip as usize);
+            if !chunk.is_empty() {
+                download.received += chunk.len() as u64;
+                return Ok(Some(chunk));
+            }
+        }
+    }
+
+    async fn request_download(
+        &self,
+        download: &mut FileDownload,
+    ) -> std::result::Result<Response, DownloadFailure> {
+        let mut request = self.download_client.get(&download.url);
+        if download.received > 0 {
+            request = request.header(http::header::RANGE, format!("bytes={}-", download.received));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| DownloadFailure::Interrupted(download_transport_error(e)))?;
+        let status = response.status();
+        tracing::debug!(status_code = %status, "storage responded");
+
+        download.skip = match status {
+            // The storage ignored the range and sent the full file.
+            StatusCode::OK => download.received,
+            StatusCode::PARTIAL_CONTENT => 0,
+            StatusCode::FORBIDDEN => return Err(DownloadFailure::Expired(download_error(status))),
+            _ => return Err(DownloadFailure::Failed(download_error(status))),
+        };
+        Ok(response)
+    }
+
+    /// Query the table again and retrieve the fresh presigned URL of a file.
+    async fn refresh_file_url(
+        &self,
+        share_name: &str,
+        schema_name: &str,
+        table_name: &str,
+        query: &TableDataQuery,
+        file_id: &str,
+    ) -> Result<String> {
+        let response = self
+            .get_table_data(share_name, schema_name, table_name, query)
+            .await?;
+        response
+            .files()
+            .iter()
+            .filter_map(TableAction::as_file)
+            .find(|file| file.id() == file_id)
+            .map(|file| file.url().to_owned())
+            .ok_or_else(|| {
+                tracing::error!(file_id, "file missing from refreshed table data");
+                DeltaSharingError::request(format!("file {file_id} is no longer part of the table"))
+            })
+    }
+
+    /// Sign and send a request, notifying the observer about its start and
+    /// end.
+    ///
+    /// With a circuit breaker configured, the request fails fast while the
+    /// breaker is open and its outcome is recorded otherwise.
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.check()?;
+        }
+
+        let mut request = request.build()?;
+        for name in self.default_headers.keys() {
+            if !request.headers().contains_key(name) {
+                for value in self.default_headers.get_all(name) {
+                    request.headers_mut().append(name, value.clone());
+                }
+            }
+        }
+        if let Some(format) = self.response_format {
+            if !request.headers().contains_key(HEADER_CAPABILITIES) {
+                request.headers_mut().insert(
+                    HEADER_CAPABILITIES,
+                    HeaderValue::from_static(format.capabilities_header()),
+                );
+            }
+        }
+        self.signer.sign(&mut request)?;
+
+        self.observer.on_request_start();
+        let start = Instant::now();
+        let response = self
+            .client
+            .execute(request)
+            .await
+            .map_err(DeltaSharingError::from);
+
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.record(match &response {
+                Ok(r) => is_retriable_status(r.status()),
+                Err(e) => e.is_retriable(),
+            });
+        }
+
+        let response = response?;
+        self.observer
+            .on_request_end(response.status(), start.elapsed());
+        Ok(response)
+    }
+
+    async fn handle_response<T: DeserializeOwned>(
+        &self,
+        response: Response,
+        resource: &str,
+    ) -> Result<T> {
+        let status_code = response.status();
+        tracing::debug!(status_code = %status_code, "server responded");
+
+        match status_code {
+            StatusCode::OK => {
+                let body = self.read_body(response).await?;
+                let res = parse_json::<T>(&body, self.strict_parsing).map_err(|e| {
+                    tracing::error!(err = ?e, "failed to parse server response");
+                    DeltaSharingError::from(e)
+                })?;
+                tracing::debug!("response parsed");
+                Ok(res)
+            }
+            _ => Err(self.handle_error_response(response, resource).await),
+        }
+    }
+
+    /// Convert an error response into an error. Not found errors name the
+    /// requested `resource`, e.g. `share my_share`.
+    async fn handle_error_response(&self, response: Response, resource: &str) -> DeltaSharingError {
+        let status_code = response.status();
+        let parse_error = |body: Result<Vec<u8>>| -> Result<ErrorResponse> {
+            let body = body?;
+            Ok(serde_json::from_slice::<ErrorResponse>(&body)?)
+        };
+
+        match status_code {
+            StatusCode::BAD_REQUEST
+            | StatusCode::UNAUTHORIZED
+            | StatusCode::FORBIDDEN
+            | StatusCode::NOT_FOUND => match parse_error(self.read_body(response).await) {
+                Ok(err) if status_code == StatusCode::NOT_FOUND => {
+                    tracing::debug!(resource, "response parsed");
+                    let message = format!("{} ({resource})", err.message());
+                    DeltaSharingError::client(status_code, err.error_code(), message)
+                }
+                Ok(err) => {
+                    tracing::debug!("response parsed");
+                    DeltaSharingError::client(status_code, err.error_code(), err.message())
+                }
+                Err(e) => e,
+            },
+            StatusCode::NOT_IMPLEMENTED => {
+                tracing::error!(resource, "endpoint not implemented");
+                DeltaSharingError::server(
+                    status_code,
+                    "NOT_IMPLEMENTED",
+                    format!("endpoint not implemented by server ({resource})"),
+                )
+            }
+            status if status.is_server_error() => {
+                let body = match self.read_body(response).await {
+                    Ok(body) => body,
+                    Err(e) => return e,
+                };
+                match serde_json::from_slice::<ErrorResponse>(&body) {
+                    Ok(err) => {
+                        tracing::debug!("response parsed");
+                        DeltaSharingError::server(status, err.error_code(), err.message())
+                    }
+                    Err(_) => {
+                        tracing::error!(status_code = %status, resource, "server error");
+                        DeltaSharingError::server(
+                            status,
+                            status_error_code(status),
+                            format!("server error ({resource})"),
+                        )
+                    }
+                }
+            }
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT => {
+                tracing::warn!(status_code = %status_code, resource, "request throttled or timed out");
+                DeltaSharingError::client(
+                    status_code,
+                    status_error_code(status_code),
+                    format!("request not handled by server ({resource})"),
+                )
+            }
+            _ => {
+                tracing::warn!(status_code = %status_code, "unexpected HTTP status");
+                DeltaSharingError::internal("unknown server response")
+            }
+        }
+    }
+
+    /// Read the full response body, failing once it exceeds the maximum
+    /// response size.
+    async fn read_body(&self, mut response: Response) -> Result<Vec<u8>> {
+        let max_size = self.max_response_size;
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            self.observer.on_bytes_downloaded(chunk.len());
+            if body.len() + chunk.len() > max_size {
+                tracing::error!(max_size, "response body exceeded max size");
+                return Err(DeltaSharingError::parse_response(
+                    "response body exceeded max size",
+                ));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    /// Read a newline delimited JSON body, failing once a single line exceeds
+    /// the maximum line size. The total size of the body is not limited.
+    async fn read_ndjson_body(&self, response: Response) -> Result<String> {
+        let body = self.read_ndjson_bytes(response).await?;
+        String::from_utf8(body).map_err(|e| {
+            tracing::error!(err = ?e, "response body is not valid UTF-8");
+            DeltaSharingError::parse_response("failed to parse server response")
+        })
+    }
+
+    async fn read_ndjson_bytes(&self, mut response: Response) -> Result<Vec<u8>> {
+        let max_line_size = self.max_line_size;
+        let mut body = Vec::new();
+        let mut line_size = 0;
+        while let Some(chunk) = response.chunk().await? {
+            self.observer.on_bytes_downloaded(chunk.len());
+            for byte in chunk.iter() {
+                if *byte == b'\n' {
+                    line_size = 0;
+                } else {
+                    line_size += 1;
+                }
+                if line_size > max_line_size {
+                    tracing::error!(max_line_size, "response line exceeded max size");
+                    return Err(DeltaSharingError::parse_response(
+                        "response line exceeded max size",
+                    ));
+                }
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+/// State of a file download that is resumed where it broke off.
+struct FileDownload {
+    url: String,
+    response: Option<Response>,
+    /// Number of bytes yielded so far.
+    received: u64,
+    /// Number of bytes still to skip, if the storage ignored the range of a
+    /// resumed download and sent the full file.
+    skip: u64,
+    resumes: u32,
+}
+
+/// Failure of a single attempt to download a file.
+enum DownloadFailure {
+    /// The presigned URL was rejected, most likely because it expired.
+    Expired(DeltaSharingError),
+    /// The connection broke off before the file was received completely.
+    Interrupted(DeltaSharingError),
+    /// The download failed for any other reason.
+    Failed(DeltaSharingError),
+}
+
+fn download_error(status: StatusCode) -> DeltaSharingError {
+    tracing::error!(status_code = %status, "failed to download file");
+    DeltaSharingError::download(Some(status), "failed to download file")
+}
+
+fn download_transport_error(err: reqwest::Error) -> DeltaSharingError {
+    tracing::error!(err = ?err, "failed to download file");
+    DeltaSharingError::download(err.status(), "failed to download file").with_source(err)
+}
+
+/// Check whether the server does not implement the requested endpoint, as
+/// opposed to not finding the requested resource.
+/// Error code for an error response without a Delta Sharing error body,
+/// derived from the status, e.g. `SERVICE_UNAVAILABLE` for 503.
+fn status_error_code(status: StatusCode) -> String {
+    status
+        .canonical_reason()
+        .unwrap_or("UNKNOWN")
+        .to_ascii_uppercase()
+        .replace(' ', "_")
+}
+
+fn is_unsupported_endpoint(err: &DeltaSharingError) -> bool {
+    match err.kind() {
+        ErrorKind::ClientError { status, code } => {
+            *status == StatusCode::NOT_FOUND && code == ERROR_CODE_ENDPOINT_NOT_FOUND
+        }
+        ErrorKind::ServerError { status, .. } => *status == StatusCode::NOT_IMPLEMENTED,
+        _ => false,
+    }
+}
+
+/// Lines of a newline delimited JSON body, read from the response on demand.
+struct NdjsonLines {
+    response: Response,
+    buffer: Vec<u8>,
+    max_line_size: usize,
+    observer: Arc<dyn Observer>,
+    bom_checked: bool,
+}
+
+impl NdjsonLines {
+    fn new(response: Response, max_line_size: usize, observer: Arc<dyn Observer>) -> Self {
+        Self {
+            response,
+            buffer: Vec::new(),
+            max_line_size,
+            observer,
+            bom_checked: false,
+        }
+    }
+
+    /// Read the next non-empty line, failing once it exceeds the maximum line
+    /// size. A leading UTF-8 byte order mark and `\r\n` line endings are
+    /// stripped.
+    async fn next_line(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if !self.bom_checked
+                && (self.buffer.len() >= UTF8_BOM.len() || self.buffer.contains(&b'\n'))
+            {
+                if self.buffer.starts_with(UTF8_BOM) {
+                    self.buffer.drain(..UTF8_BOM.len());
+                }
+                self.bom_checked = true;
+            }
+            if let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+                let mut line = self.buffer.drain(..=end).collect::<Vec<_>>();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                return Ok(Some(line));
+            }
+            if self.buffer.len() > self.max_line_size {
+                tracing::error!(
+                    max_line_size = self.max_line_size,
+                    "response line exceeded max size"
+                );
+                return Err(DeltaSharingError::parse_response(
+                    "response line exceeded max size",
+                ));
+            }
+
+            match self.response.chunk().await? {
+                Some(chunk) => {
+                    self.observer.on_bytes_downloaded(chunk.len());
+                    self.buffer.extend_from_slice(&chunk);
+                }
+                None => {
+                    let mut line = std::mem::take(&mut self.buffer);
+                    if !self.bom_checked && line.starts_with(UTF8_BOM) {
+                        line.drain(..UTF8_BOM.len());
+                    }
+                    self.bom_checked = true;
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        return Ok(None);
+                    }
+                    return Ok(Some(line));
+                }
+            }
+        }
+    }
+}
+
+fn table_version_header(response: &Response) -> Result<u64> {
+    response
+        .headers()
+        .get(HEADER_TABLE_VERSION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .ok_or(DeltaSharingError::parse_response(
+            "parsing delta-table-version header failed",
+        ))
+}
+
+/// Redirect policy for requests to the sharing server, see
+/// [`DeltaSharingClientBuilder::with_follow_redirects`].
+#[derive(Debug)]
+enum RedirectPolicy {
+    Custom(redirect::Policy),
+    Follow(usize),
+}
+
+/// Redirect policy following at most `max_redirects` redirects, except those
+/// that would forward the authorization header to another origin.
+///
+/// reqwest drops the authorization header itself when a redirect changes the
+/// host or port, but keeps it when only the scheme changes. Redirects to
+/// another origin are only followed if `follow_cross_origin` is set.
+fn token_safe_redirect_policy(max_redirects: usize, follow_cross_origin: bool) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects {
+            return attempt.error("too many redirects");
+        }
+        let Some(previous) = attempt.previous().last() else {
+            return attempt.follow();
+        };
+        let target = attempt.url();
+        let same_host = target.host_str() == previous.host_str()
+            && target.port_or_known_default() == previous.port_or_known_default();
+        if same_host && target.scheme() != previous.scheme() {
+            tracing::warn!("not following redirect changing only the scheme");
+            return attempt.stop();
+        }
+        if !same_host && !follow_cross_origin {
+            tracing::warn!(
+                "not following redirect to another origin with signed or default headers"
+            );
+            return attempt.stop();
+        }
+        attempt.follow()
+    })
+}
+
+/// Builder for a [`DeltaSharingClient`].
+///
+/// The connection pool settings are forwarded to the underlying HTTP client.
+/// When downloading many files concurrently, set `pool_max_idle_per_host` to
+/// roughly the download concurrency (e.g. 32 or 64) so connections to the
+/// storage host are reused instead of re-established, and keep the
+/// `pool_idle_timeout` below the idle timeout of the storage host (e.g. 30
+/// seconds) to avoid reusing connections the host already closed.
+#[must_use = "builders do nothing unless `build` is called"]
+pub struct DeltaSharingClientBuilder {
+    profile: Profile,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    resolve_overrides: Vec<(String, SocketAddr)>,
+    redirect_policy: Option<RedirectPolicy>,
+    download_redirect_policy: Option<redirect::Policy>,
+    sort_results: bool,
+    sort_files: bool,
+    strict_parsing: bool,
+    get_share_fallback: bool,
+    validate_predicates: bool,
+    response_format: Option<ResponseFormat>,
+    default_page_size: Option<u32>,
+    max_list_results: usize,
+    resume_on_expiry: bool,
+    max_response_size: usize,
+    max_line_size: usize,
+    url_rewrite: Option<UrlRewrite>,
+    observer: Arc<dyn Observer>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    default_headers: Result<HeaderMap>,
+    token_provider: Option<Arc<dyn DynTokenProvider>>,
+    circuit_breaker: Option<(u32, Duration, Duration)>,
+}
+
+impl fmt::Debug for DeltaSharingClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeltaSharingClientBuilder")
+            .field("profile", &self.profile)
+            .field("pool_max_idle_per_host", &self.pool_max_idle_per_host)
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("resolve_overrides", &self.resolve_overrides)
+            .field("redirect_policy", &self.redirect_policy)
+            .field("download_redirect_policy", &self.download_redirect_policy)
+            .field("sort_results", &self.sort_results)
+            .field("sort_files", &self.sort_files)
+            .field("strict_parsing", &self.strict_parsing)
+            .field("get_share_fallback", &self.get_share_fallback)
+            .field("validate_predicates", &self.validate_predicates)
+            .field("response_format", &self.response_format)
+            .field("default_page_size", &self.default_page_size)
+            .field("max_list_results", &self.max_list_results)
+            .field("resume_on_expiry", &self.resume_on_expiry)
+            .field("max_response_size", &self.max_response_size)
+            .field("max_line_size", &self.max_line_size)
+            .field("url_rewrite", &self.url_rewrite.is_some())
+            .field("observer", &self.observer)
+            .field("signer", &self.signer)
+            .field(
+                "default_headers",
+                &self.default_headers.as_ref().map(HeaderMap::keys),
+            )
+            .field("token_provider", &self.token_provider.is_some())
+            .field("circuit_breaker", &self.circuit_breaker)
+            .finish()
+    }
+}
+
+impl DeltaSharingClientBuilder {
+    /// Create a new builder for the given profile.
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            resolve_overrides: vec![],
+            redirect_policy: None,
+            download_redirect_policy: None,
+            sort_results: false,
+            sort_files: false,
+            strict_parsing: false,
+            get_share_fallback: false,
+            validate_predicates: false,
+            response_format: None,
+            default_page_size: None,
+            max_list_results: DEFAULT_MAX_LIST_RESULTS,
+            resume_on_expiry: false,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            max_line_size: DEFAULT_MAX_LINE_SIZE,
+            url_rewrite: None,
+            observer: Arc::new(NoopObserver),
+            signer: None,
+            default_headers: Ok(HeaderMap::new()),
+            token_provider: None,
+            circuit_breaker: None,
+        }
+    }
+
+    /// Set the maximum number of idle connections kept alive per host.
+    pub fn with_pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max_idle);
+        self
+    }
+
+    /// Set the duration after which idle connections are closed.
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Resolve `domain` to `addr` instead of looking it up in DNS.
+    ///
+    /// Requests keep using the domain for TLS, so the server certificate is
+    /// validated against the domain rather than the address. Use this to
+    /// reach a server through an address whose certificate names another
+    /// host. The port of `addr` is ignored; requests use the port of the URL.
+    pub fn with_resolve(mut self, domain: impl Into<String>, addr: SocketAddr) -> Self {
+        self.resolve_overrides.push((domain.into(), addr));
+        self
+    }
+
+    /// Set the redirect policy for requests to the sharing server.
+    ///
+    /// By default redirects are not followed: a redirect response fails the
+    /// request instead. Following redirects could send the bearer token to
+    /// wherever the redirect points, so only relax this for servers known to
+    /// redirect within trusted hosts.
+    pub fn with_redirect_policy(mut self, policy: redirect::Policy) -> Self {
+        self.redirect_policy = Some(RedirectPolicy::Custom(policy));
+        self
+    }
+
+    /// Follow up to `max_redirects` redirects of requests to the sharing
+    /// server without leaking the bearer token to another origin.
+    ///
+    /// The authorization header is dropped when a redirect changes the host
+    /// or port. A redirect changing only the scheme, e.g. from `https` to
+    /// `http`, is not followed, as the header would be kept. Headers added by
+    /// a [request signer](Self::with_request_signer) or
+    /// [default headers](Self::with_default_header) are not dropped, so with
+    /// either configured, redirects to another origin are not followed. This
+    /// replaces a policy set with [`Self::with_redirect_policy`].
+    pub fn with_follow_redirects(mut self, max_redirects: usize) -> Self {
+        self.redirect_policy = Some(RedirectPolicy::Follow(max_redirects));
+        self
+    }
+
+    /// Set the redirect policy for downloading presigned file URLs, see
+    /// [`DeltaSharingClient::download_file`] and
+    /// [`DeltaSharingClient::http_client`].
+    ///
+    /// Object stores sometimes redirect presigned URLs, so by default up to 10
+    /// redirects are followed. File downloads carry no credentials besides
+    /// the presigned URL itself.
+    pub fn with_download_redirect_policy(mut self, policy: redirect::Policy) -> Self {
+        self.download_redirect_policy = Some(policy);
+        self
+    }
+
+    /// Sort the results of the high-level list methods by name.
+    ///
+    /// By default the results are returned in the order of the server.
+    pub fn with_sorted_results(mut self, sort: bool) -> Self {
+        self.sort_results = sort;
+        self
+    }
+
+    /// Look up shares by listing all shares when the server does not
+    /// implement the endpoint to get a single share.
+    ///
+    /// Older servers only support listing shares. With the fallback enabled,
+    /// [`DeltaSharingClient::get_share`] scans the listed shares for the
+    /// share name when the server responds with `501 Not Implemented` or
+    /// with a `404 Not Found` that is not a Delta Sharing error response.
+    /// A `404` naming a missing share still yields `None` without listing.
+    /// Disabled by default.
+    pub fn with_get_share_fallback(mut self, fallback: bool) -> Self {
+        self.get_share_fallback = fallback;
+        self
+    }
+
+    /// Check the columns referenced by the predicate hints of data queries
+    /// against the table schema before sending the query.
+    ///
+    /// See [`TableDataQuery::validate_predicate_columns`]. Validation costs an
+    /// extra metadata request per query with predicate hints. Disabled by
+    /// default.
+    pub fn with_predicate_validation(mut self, validate: bool) -> Self {
+        self.validate_predicates = validate;
+        self
+    }
+
+    /// Request table query responses in the given format.
+    ///
+    /// The format is advertised in the `delta-sharing-capabilities` header of
+    /// every request. With [`ResponseFormat::Auto`] both formats are
+    /// advertised and the response is parsed in the format the server chose;
+    /// inspect the returned actions to tell which one it was. With
+    /// [`ResponseFormat::Delta`], metadata, data and changes queries fail with
+    /// a "feature not supported" error if the server capabilities, detected
+    /// once and cached, do not include the delta format. By default the
+    /// header is not sent and servers respond in the parquet format.
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
+        self
+    }
+
+    /// Sort the files returned by [`DeltaSharingClient::get_table_data`] by
+    /// table version, then by file id.
+    ///
+    /// By default files are returned in the order of the server, which may
+    /// differ between requests. Sorting makes reads reproducible. The full
+    /// list of files is buffered either way; the streaming and `*_raw`
+    /// methods are not affected.
+    pub fn with_sorted_files(mut self, sort: bool) -> Self {
+        self.sort_files = sort;
+        self
+    }
+
+    /// Reject server responses containing fields unknown to this client.
+    ///
+    /// By default unknown fields are ignored so newer servers remain
+    /// compatible. Strict parsing surfaces protocol drift as parse errors
+    /// naming the unexpected field instead.
+    pub fn with_strict_parsing(mut self, strict: bool) -> Self {
+        self.strict_parsing = strict;
+        self
+    }
+
+    /// Set the number of results requested per page by the high-level list
+    /// methods.
+    ///
+    /// By default no page size is sent and the server picks one. Values above
+    /// [`DEFAULT_MAX_RESULTS_LIMIT`](crate::request::pagination::DEFAULT_MAX_RESULTS_LIMIT)
+    /// are clamped. The `*_with_page_size` list methods override the default
+    /// per call, and the `*_raw` list methods use the pagination passed to
+    /// them.
+    pub fn with_default_page_size(mut self, page_size: u32) -> Self {
+        self.default_page_size = Some(page_size);
+        self
+    }
+
+    /// Set the maximum number of items collected by the high-level list
+    /// methods across all pages.
+    ///
+    /// Listing fails once more items are returned, which protects against a
+    /// server that never stops paginating. Defaults to 1,000,000.
+    pub fn with_max_list_results(mut self, max_results: usize) -> Self {
+        self.max_list_results = max_results;
+        self
+    }
+
+    /// Resume file downloads that break off or whose presigned URL expired.
+    ///
+    /// See [`DeltaSharingClient::download_file`]. Disabled by default.
+    pub fn with_resume_on_expiry(mut self, resume: bool) -> Self {
+        self.resume_on_expiry = resume;
+        self
+    }
+
+    /// Set the maximum size in bytes of a JSON (error) response body.
+    ///
+    /// Larger bodies are rejected instead of buffered. Defaults to 16 MiB.
+    pub fn with_max_response_size(mut self, max_size: usize) -> Self {
+        self.max_response_size = max_size;
+        self
+    }
+
+    /// Set the maximum size in bytes of a single line in a newline delimited
+    /// JSON response body. Defaults to 16 MiB.
+    pub fn with_max_line_size(mut self, max_size: usize) -> Self {
+        self.max_line_size = max_size;
+        self
+    }
+
+    /// Rewrite the presigned URLs of the file actions returned by data and
+    /// changes queries, e.g. to replace an internal storage hostname with a
+    /// publicly routable one.
+    pub fn with_url_rewrite(
+        mut self,
+        rewrite: impl Fn(Url) -> Url + Send + Sync + 'static,
+    ) -> Self {
+        self.url_rewrite = Some(Arc::new(rewrite));
+        self
+    }
+
+    /// Register an observer that is notified about requests and downloaded
+    /// bytes, e.g. to collect metrics.
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Register a signer that signs every request after it was authorized,
+    /// e.g. for gateways that require signed requests.
+    pub fn with_request_signer(mut self, signer: Arc<dyn RequestSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Add a header that is sent with every request to the sharing server,
+    /// e.g. a tenant id required by the deployment.
+    ///
+    /// Headers set by the client itself, such as the authorization header,
+    /// take precedence. Presigned file URLs are downloaded without these
+    /// headers. An invalid header name or value fails [`Self::build`].
+    pub fn with_default_header(mut self, name: &str, value: &str) -> Self {
+        let header = HeaderName::try_from(name)
+            .ok()
+            .zip(HeaderValue::try_from(value).ok());
+        match (&mut self.default_headers, header) {
+            (Ok(headers), Some((name, value))) => {
+                headers.append(name, value);
+            }
+            (Ok(_), None) => {
+                tracing::error!(name, "invalid default header");
+                self.default_headers = Err(DeltaSharingError::request(format!(
+                    "invalid default header `{name}`"
+                )));
+            }
+            (Err(_), _) => {}
+        }
+        self
+    }
+
+    /// Use a token provider to authenticate requests instead of the token in
+    /// the profile.
+    ///
+    /// The injected provider takes precedence over a bearer token in the
+    /// profile. Profiles of type
+    /// [`ProfileType::External`](crate::profile::ProfileType::External)
+    /// require a provider; anonymous profiles never send a token.
+    pub fn with_token_provider(mut self, provider: Arc<dyn DynTokenProvider>) -> Self {
+        self.token_provider = Some(provider);
+        self
+    }
+
+    /// Enable a circuit breaker that fails requests fast while the server
+    /// appears to be down.
+    ///
+    /// After `failure_threshold` consecutive retriable failures (server
+    /// errors, throttling, timeouts or connection errors) within `window`,
+    /// all requests fail immediately with a server error for `cooldown`.
+    /// Any successful request resets the failure count. Disabled by default.
+    pub fn with_circuit_breaker(
+        mut self,
+        failure_threshold: u32,
+        window: Duration,
+        cooldown: Duration,
+    ) -> Self {
+        self.circuit_breaker = Some((failure_threshold, window, cooldown));
+        self
+    }
+
+    /// Build the client.
+    pub fn build(self) -> Result<DeltaSharingClient> {
+        let build_client = |redirect_policy: redirect::Policy| {
+            let mut builder = reqwest::Client::builder().redirect(redirect_policy);
+            if let Some(max_idle) = self.pool_max_idle_per_host {
+                builder = builder.pool_max_idle_per_host(max_idle);
+            }
+            if let Some(timeout) = self.pool_idle_timeout {
+                builder = builder.pool_idle_timeout(timeout);
+            }
+            for (domain, addr) in &self.resolve_overrides {
+                builder = builder.resolve(domain, *addr);
+            }
+
+            builder.build().map_err(|e| {
+                tracing::error!(err = ?e, "failed to build HTTP client");
+                DeltaSharingError::internal("failed to build HTTP client")
+            })
+        };
+        let default_headers = self.default_headers?;
+        let redirect_policy = match self.redirect_policy {
+            Some(RedirectPolicy::Custom(policy)) => policy,
+            Some(RedirectPolicy::Follow(max_redirects)) => {
+                let follow_cross_origin = self.signer.is_none() && default_headers.is_empty();
+                token_safe_redirect_policy(max_redirects, follow_cross_origin)
+            }
+            None => redirect::Policy::none(),
+        };
+        let client = build_client(redirect_policy)?;
+        let download_client = build_client(self.download_redirect_policy.unwrap_or_default())?;
+
+        Ok(DeltaSharingClient {
+            client,
+            download_client,
+            profile: self.profile,
+            sort_results: self.sort_results,
+            sort_files: self.sort_files,
+            strict_parsing: self.strict_parsing,
+            get_share_fallback: self.get_share_fallback,
+            validate_predicates: self.validate_predicates,
+            response_format: self.response_format,
+            default_page_size: self.default_page_size,
+            max_list_results: self.max_list_results,
+            resume_on_expiry: self.resume_on_expiry,
+            max_response_size: self.max_response_size,
+            max_line_size: self.max_line_size,
+            url_rewrite: self.url_rewrite,
+            observer: self.observer,
+            signer: self.signer.unwrap_or_else(|| Arc::new(NoopSigner)),
+            default_headers,
+            token_provider: self.token_provider,
+            circuit_breaker: self.circuit_breaker.map(|(threshold, window, cooldown)| {
+                CircuitBreaker::new(threshold, window, cooldown)
+            }),
+            capabilities: OnceLock::new(),
+        })
+    }
+}
+
+trait AuthorizationExt
+where
+    Self: Sized,
+{
+    async fn authorize(
+        self,
+        profile: &Profile,
+        token_provider: Option<&dyn DynTokenProvider>,
+    ) -> Result<Self>;
+}
+
+impl AuthorizationExt for RequestBuilder {
+    async fn authorize(
+        self,
+        profile: &Profile,
+        token_provider: Option<&dyn DynTokenProvider>,
+    ) -> Result<Self> {
+        if profile.is_anonymous() {
+            return Ok(self);
+        }
+        let token = match token_provider {
+            Some(provider) => provider.provide_token_boxed().await?,
+            None => profile.provide_token().await?,
+        };
+        let auth_req = self.bearer_auth(token);
+        Ok(auth_req)
+    }
+}
+
+impl From<reqwest::Error> for DeltaSharingError {
     fn from(e: reqwest::Error) -> Self {
-        DeltaSharingError::client(StatusCode::INTERNAL_SERVER_ERROR, "", e.to_string())
+        tracing::error!(err = ?e, "request to sharing server failed");
+        let err = if e.is_builder() {
+            DeltaSharingError::request("failed to build request")
+        } else if e.is_timeout() {
+            DeltaSharingError::transport(true, "request to sharing server timed out")
+        } else if e.is_connect() {
+            DeltaSharingError::transport(false, "failed to connect to sharing server")
+        } else if e.is_request() || e.is_body() {
+            DeltaSharingError::transport(false, "connection to sharing server failed")
+        } else {
+            DeltaSharingError::internal("request to sharing server failed")
+        };
+        err.with_source(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::{DateTime, Utc};
+    use static_assertions::assert_impl_all;
+
+    use super::*;
+    use crate::profile::ProfileType;
+    use crate::request::query::VersionRange;
+    use crate::test_util::{MockResponse, MockServer};
+
+    assert_impl_all!(DeltaSharingClient: Send, Sync);
+    assert_impl_all!(DeltaSharingClientBuilder: Send, Sync);
+
+    fn profile() -> Profile {
+        Profile::new_bearer_token(1, "https://sharing.delta.io/delta-sharing/", "token", None)
+            .unwrap()
+    }
+
+    #[test]
+    fn build_client_with_pool_settings() {
+        let builder = DeltaSharingClient::builder(profile())
+            .with_pool_max_idle_per_host(64)
+            .with_pool_idle_timeout(Duration::from_secs(30));
+        assert_eq!(builder.pool_max_idle_per_host, Some(64));
+        assert_eq!(builder.pool_idle_timeout, Some(Duration::from_secs(30)));
+
+        let client = builder.build();
+        assert!(client.is_ok());
+    }
+
+    #[tokio::test]
+    async fn resolve_domain_override() {
+        let server = MockServer::start(|_| MockResponse::ok(r#"{"items":[]}"#)).await;
+        let port = server.url().port().unwrap();
+        let endpoint = format!("http://sharing.internal:{port}/");
+        let profile = Profile::new_bearer_token(1, endpoint.as_str(), "token", None).unwrap();
+
+        let client = DeltaSharingClient::builder(profile)
+            .with_resolve("sharing.internal", SocketAddr::from(([127, 0, 0, 1], 0)))
+            .build()
+            .unwrap();
+        client.list_shares().await.unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(
+            request.header("Host"),
+            Some(format!("sharing.internal:{port}").as_str())
+        );
+    }
+
+    #[test]
+    fn sort_results_when_enabled() {
+        let names = vec!["b", "c", "a"];
+
+        let client = DeltaSharingClient::new(profile()).unwrap();
+        assert_eq!(client.sorted(names.clone()), vec!["b", "c", "a"]);
+
+        let client = DeltaSharingClient::builder(profile())
+            .with_sorted_results(true)
+            .build()
+            .unwrap();
+        assert_eq!(client.sorted(names), vec!["a", "b", "c"]);
+    }
+
+    fn response(status: StatusCode, body: &'static str) -> Response {
+        http::Response::builder()
+            .status(status)
+            .body(body)
+            .unwrap()
+            .into()
+    }
+
+    fn client_with_limits(max_response_size: usize, max_line_size: usize) -> DeltaSharingClient {
+        DeltaSharingClient::builder(profile())
+            .with_max_response_size(max_response_size)
+            .with_max_line_size(max_line_size)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reject_oversized_response_body() {
+        let body = r#"{"items":[],"nextPageToken":null}"#;
+
+        let res = client_with_limits(64, 64)
+            .handle_response::<ListSharesResponse>(response(StatusCode::OK, body), "shares")
+            .await;
+        assert!(res.is_ok());
+
+        let err = client_with_limits(10, 64)
+            .handle_response::<ListSharesResponse>(response(StatusCode::OK, body), "shares")
+            .await
+            .unwrap_err();
+        assert_eq!(err.message(), "response body exceeded max size");
+    }
+
+    #[tokio::test]
+    async fn reject_oversized_error_body() {
+        let body = r#"{"errorCode":"NOT_FOUND","message":"share not found"}"#;
+
+        let err = client_with_limits(64, 64)
+            .handle_error_response(response(StatusCode::NOT_FOUND, body), "share s1")
+            .await;
+        assert!(err.is_not_found());
+
+        let err = client_with_limits(10, 64)
+            .handle_error_response(response(StatusCode::NOT_FOUND, body), "share s1")
+            .await;
+        assert_eq!(err.message(), "response body exceeded max size");
+    }
+
+    #[tokio::test]
+    async fn reject_oversized_ndjson_line() {
+        let body = "{\"a\":1}\n{\"b\":2}\n";
+
+        let res = client_with_limits(64, 7)
+            .read_ndjson_body(response(StatusCode::OK, body))
+            .await;
+        assert_eq!(res.unwrap(), body);
+
+        let err = client_with_limits(64, 6)
+            .read_ndjson_body(response(StatusCode::OK, body))
+            .await
+            .unwrap_err();
+        assert_eq!(err.message(), "response line exceeded max size");
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingObserver {
+        started: AtomicUsize,
+        ended: AtomicUsize,
+        bytes: AtomicUsize,
+    }
+
+    impl Observer for CountingObserver {
+        fn on_request_start(&self) {
+            self.started.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_request_end(&self, _status: StatusCode, _duration: Duration) {
+            self.ended.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_bytes_downloaded(&self, bytes: usize) {
+            self.bytes.fetch_add(bytes, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn observe_requests_per_page() {
+        let first_page = r#"{"items":[{"name":"a"}],"nextPageToken":"next"}"#;
+        let last_page = r#"{"items":[{"name":"b"}]}"#;
+        let server = MockServer::start(move |req| {
+            if req.target.contains("pageToken=next") {
+                MockResponse::ok(last_page)
+            } else {
+                MockResponse::ok(first_page)
+            }
+        })
+        .await;
+        let observer = Arc::new(CountingObserver::default());
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_observer(observer.clone())
+            .build()
+            .unwrap();
+
+        let shares = client.list_shares().await.unwrap();
+        assert_eq!(shares.len(), 2);
+        assert_eq!(observer.started.load(Ordering::SeqCst), 2);
+        assert_eq!(observer.ended.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            observer.bytes.load(Ordering::SeqCst),
+            first_page.len() + last_page.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn abort_on_repeated_page_token() {
+        let server = MockServer::start(|_| {
+            MockResponse::ok(r#"{"items":[{"name":"share"}],"nextPageToken":"same"}"#)
+        })
+        .await;
+
+        let err = server.client().list_shares().await.unwrap_err();
+        assert_eq!(
+            err.message(),
+            "server returned the same page token twice in a row"
+        );
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn stop_after_full_page_without_token() {
+        let server =
+            MockServer::start(|_| MockResponse::ok(r#"{"items":[{"name":"a"},{"name":"b"}]}"#))
+                .await;
+
+        let shares = server.client().list_shares().await.unwrap();
+        assert_eq!(shares.len(), 2);
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn distinguish_download_errors() {
+        let server = MockServer::start(|req| match req.target.as_str() {
+            "/files/expired" => MockResponse::new(403, "expired"),
+            _ => MockResponse::new(500, "unavailable"),
+        })
+        .await;
+        let file = |name: &str| {
+            serde_json::from_str::<TableAction>(&format!(
+                r#"{{"file":{{"url":"{}files/{name}","id":"c1","partitionValues":{{}},"size":10}}}}"#,
+                server.url()
+            ))
+            .unwrap()
+        };
+        let client = server.client();
+        let query = TableDataQuery::default();
+
+        let expired = file("expired");
+        let err = client
+            .download_file(
+                "share",
+                "schema",
+                "table",
+                &query,
+                expired.as_file().unwrap(),
+            )
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        assert!(err.is_download_error());
+        assert!(!err.is_auth_error());
+        assert!(!err.is_retriable());
+        assert_eq!(
+            err.to_string(),
+            "[DOWNLOAD_ERROR] 403 Forbidden failed to download file"
+        );
+
+        let unavailable = file("unavailable");
+        let err = client
+            .download_file_range(unavailable.as_file().unwrap(), 0..4)
+            .await
+            .unwrap_err();
+        assert!(err.is_download_error());
+        assert!(err.is_retriable());
+        assert_eq!(
+            err.to_string(),
+            "[DOWNLOAD_ERROR] 500 Internal Server Error failed to download file"
+        );
+
+        let file = serde_json::from_str::<TableAction>(
+            r#"{"file":{"url":"http://127.0.0.1:1/files/part-0","id":"c1","partitionValues":{},"size":10}}"#,
+        )
+        .unwrap();
+        let err = client
+            .download_file_range(file.as_file().unwrap(), 0..4)
+            .await
+            .unwrap_err();
+        assert!(err.is_download_error());
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.downcast_ref::<reqwest::Error>().is_some());
+    }
+
+    #[tokio::test]
+    async fn download_byte_range() {
+        let server = MockServer::start(|req| match (req.target.as_str(), req.header("Range")) {
+            ("/files/part-0", Some("bytes=6-9")) => MockResponse::new(206, "PAR1"),
+            ("/files/part-1", _) => MockResponse::ok("PAR1....PAR1"),
+            _ => MockResponse::new(416, ""),
+        })
+        .await;
+        let file = |name: &str| {
+            serde_json::from_str::<TableAction>(&format!(
+                r#"{{"file":{{"url":"{}files/{name}","id":"c1","partitionValues":{{}},"size":10}}}}"#,
+                server.url()
+            ))
+            .unwrap()
+        };
+        let client = server.client();
+
+        let part_0 = file("part-0");
+        let bytes = client
+            .download_file_range(part_0.as_file().unwrap(), 6..10)
+            .await
+            .unwrap();
+        assert_eq!(bytes, "PAR1");
+
+        let part_1 = file("part-1");
+        let err = client
+            .download_file_range(part_1.as_file().unwrap(), 6..10)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "storage ignored the range request and sent the full file"
+        );
+
+        let bytes = client
+            .download_file_range(part_0.as_file().unwrap(), 3..3)
+            .await
+            .unwrap();
+        assert!(bytes.is_empty());
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn resume_download_after_url_expiry() {
+        let server = MockServer::start(|req| {
+            match (req.target.as_str(), req.header("Range")) {
+                // The connection breaks off after the first half of the file.
+                ("/files/part-0", None) => MockResponse::ok("hello").with_content_length(10),
+                ("/files/part-0", Some(_)) => MockResponse::new(403, "expired"),
+                ("/shares/share/schemas/schema/tables/table/query", _) => {
+                    let host = req.header("Host").unwrap();
+                    let url = format!("http://{host}/files/part-0-fresh");
+                    MockResponse::ok(format!(
+                        "{}\n{}\n{}",
+                        r#"{"protocol":{"minReaderVersion":1}}"#,
+                        r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+                        format!(r#"{{"file":{{"url":"{url}","id":"c1","partitionValues":{{}},"size":10}}}}"#),
+                    ))
+                    .with_header(HEADER_TABLE_VERSION, "1")
+                }
+                ("/files/part-0-fresh", Some("bytes=5-")) => MockResponse::new(206, "world"),
+                _ => MockResponse::new(404, ""),
+            }
+        })
+        .await;
+        let file = serde_json::from_str::<TableAction>(&format!(
+            r#"{{"file":{{"url":"{}files/part-0","id":"c1","partitionValues":{{}},"size":10}}}}"#,
+            server.url()
+        ))
+        .unwrap();
+        let query = TableDataQuery::default();
+
+        let client = server.client();
+        let result = client
+            .download_file("share", "schema", "table", &query, file.as_file().unwrap())
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(result.is_err());
+        assert_eq!(server.request_count(), 1);
+
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_resume_on_expiry(true)
+            .build()
+            .unwrap();
+        let chunks = client
+            .download_file("share", "schema", "table", &query, file.as_file().unwrap())
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(chunks, vec![Bytes::from("hello"), Bytes::from("world")]);
+
+        let targets = server
+            .requests()
+            .into_iter()
+            .skip(1)
+            .map(|req| req.target)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                "/files/part-0",
+                "/files/part-0",
+                "/shares/share/schemas/schema/tables/table/query",
+                "/files/part-0-fresh",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn resume_download_ignoring_range() {
+        let server = MockServer::start(|req| match req.header("Range") {
+            None => MockResponse::ok("hello").with_content_length(10),
+            Some(_) => MockResponse::ok("helloworld"),
+        })
+        .await;
+        let file = serde_json::from_str::<TableAction>(&format!(
+            r#"{{"file":{{"url":"{}files/part-0","id":"c1","partitionValues":{{}},"size":10}}}}"#,
+            server.url()
+        ))
+        .unwrap();
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_resume_on_expiry(true)
+            .build()
+            .unwrap();
+
+        let chunks = client
+            .download_file(
+                "share",
+                "schema",
+                "table",
+                &TableDataQuery::default(),
+                file.as_file().unwrap(),
+            )
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(chunks.concat(), b"helloworld");
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn send_default_headers() {
+        let server = MockServer::start(|_| MockResponse::ok(r#"{"items":[]}"#)).await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_default_header("X-Tenant-Id", "tenant-1")
+            .with_default_header("Authorization", "Bearer other")
+            .build()
+            .unwrap();
+
+        client.list_shares().await.unwrap();
+        let request = &server.requests()[0];
+        assert_eq!(request.header("X-Tenant-Id"), Some("tenant-1"));
+        assert_eq!(request.header("Authorization"), Some("Bearer token"));
+
+        let err = DeltaSharingClient::builder(server.profile())
+            .with_default_header("X-Tenant-Id", "tenant\n1")
+            .build()
+            .unwrap_err();
+        assert_eq!(err.message(), "invalid default header `X-Tenant-Id`");
+    }
+
+    #[tokio::test]
+    async fn name_resource_in_not_found_error() {
+        let server = MockServer::start(|_| {
+            MockResponse::new(
+                404,
+                r#"{"errorCode":"NOT_FOUND","message":"share not found"}"#,
+            )
+        })
+        .await;
+        let client = server.client();
+
+        let err = client.get_share_raw("vaccine_share").await.unwrap_err();
+        assert!(err.is_not_found());
+        assert_eq!(err.message(), "share not found (share vaccine_share)");
+        assert_eq!(client.get_share("vaccine_share").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn get_versions_of_many_tables() {
+        let server = MockServer::start(|req| match req.target.as_str() {
+            "/shares/share/schemas/schema/tables/missing/version" => MockResponse::new(
+                404,
+                r#"{"errorCode":"NOT_FOUND","message":"table not found"}"#,
+            ),
+            "/shares/share/schemas/schema/tables/boston/version" => {
+                MockResponse::ok("").with_header(HEADER_TABLE_VERSION, "7")
+            }
+            _ => MockResponse::ok("").with_header(HEADER_TABLE_VERSION, "3"),
+        })
+        .await;
+        let client = server.client();
+        let tables = [
+            "share.schema.cov",
+            "share.schema.missing",
+            "share.schema.boston",
+        ]
+        .iter()
+        .map(|t| t.parse::<TableRef>().unwrap())
+        .collect::<Vec<_>>();
+
+        let versions = client.get_table_versions(&tables, 2).await;
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0].0, tables[0]);
+        assert_eq!(versions[0].1.as_ref().unwrap(), &3);
+        assert_eq!(versions[1].0, tables[1]);
+        assert!(versions[1].1.as_ref().unwrap_err().is_not_found());
+        assert_eq!(versions[2].0, tables[2]);
+        assert_eq!(versions[2].1.as_ref().unwrap(), &7);
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn get_latest_cdf_version() {
+        let server = MockServer::start(|req| match req.target.as_str() {
+            "/shares/share/schemas/schema/tables/cdf/metadata" => MockResponse::ok(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[],"configuration":{"delta.enableChangeDataFeed":"true"}}}"#,
+            ))
+            .with_header(HEADER_TABLE_VERSION, "5"),
+            "/shares/share/schemas/schema/tables/plain/metadata" => MockResponse::ok(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+            ))
+            .with_header(HEADER_TABLE_VERSION, "5"),
+            _ => MockResponse::ok("").with_header(HEADER_TABLE_VERSION, "7"),
+        })
+        .await;
+        let client = server.client();
+
+        let latest = client
+            .latest_cdf_version("share", "schema", "cdf")
+            .await
+            .unwrap();
+        assert_eq!(latest, 7);
+        let targets = server
+            .requests()
+            .into_iter()
+            .map(|req| req.target)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                "/shares/share/schemas/schema/tables/cdf/metadata",
+                "/shares/share/schemas/schema/tables/cdf/version",
+            ]
+        );
+
+        let err = client
+            .latest_cdf_version("share", "schema", "plain")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "change data feed is not enabled on table share.schema.plain"
+        );
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn get_table_version_as_of_timestamp() {
+        let server = MockServer::start(|req| {
+            let version = if req.target.contains("startingTimestamp") {
+                "2"
+            } else {
+                "5"
+            };
+            MockResponse::ok("").with_header(HEADER_TABLE_VERSION, version)
+        })
+        .await;
+        let client = server.client();
+        let ts = DateTime::parse_from_rfc3339("2021-08-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let latest = client
+            .get_table_version("share", "schema", "table", &TableVersionQuery::Latest)
+            .await
+            .unwrap();
+        assert_eq!(latest.version(), 5);
+        let as_of = client
+            .get_table_version("share", "schema", "table", &ts.into())
+            .await
+            .unwrap();
+        assert_eq!(as_of.version(), 2);
+
+        let targets = server
+            .requests()
+            .into_iter()
+            .map(|req| req.target)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                "/shares/share/schemas/schema/tables/table/version",
+                "/shares/share/schemas/schema/tables/table/version?startingTimestamp=2021-08-01T00%3A00%3A00Z",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_pages_of_data_query() {
+        let server = MockServer::start(|req| {
+            let mut body = String::from(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+                "\n",
+            ));
+            if req.body.contains(r#""pageToken":"page-2""#) {
+                body.push_str(r#"{"file":{"url":"https://example.com/part-1.parquet","id":"c2","partitionValues":{},"size":573}}"#);
+                body.push_str("\n");
+                body.push_str(r#"{"endStreamAction":{"minUrlExpirationTimestamp":1652140800000}}"#);
+            } else {
+                body.push_str(r#"{"file":{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{},"size":573}}"#);
+                body.push_str("\n");
+                body.push_str(r#"{"endStreamAction":{"nextPageToken":"page-2","minUrlExpirationTimestamp":1652140800000}}"#);
+            }
+            MockResponse::ok(body).with_header(HEADER_TABLE_VERSION, "1")
+        })
+        .await;
+
+        let query = TableDataQuery::default().with_max_files(1);
+        let data = server
+            .client()
+            .get_table_data("share", "schema", "table", &query)
+            .await
+            .unwrap();
+
+        let ids = data
+            .files()
+            .iter()
+            .filter_map(TableAction::as_file)
+            .map(|f| f.id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["c1", "c2"]);
+        assert_eq!(data.next_page_token(), None);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body, r#"{"maxFiles":1}"#);
+        assert_eq!(requests[1].body, r#"{"maxFiles":1,"pageToken":"page-2"}"#);
+    }
+
+    #[tokio::test]
+    async fn classify_unavailable_and_throttled_server() {
+        let server = MockServer::start(|req| match req.target.as_str() {
+            "/shares?maxResults=1" => MockResponse::new(503, "<html>Unavailable</html>"),
+            _ => MockResponse::new(429, ""),
+        })
+        .await;
+        let client = server.client();
+
+        let err = client.ping().await.unwrap_err();
+        assert!(err.is_retriable());
+        assert_eq!(
+            err.to_string(),
+            "[SERVER_ERROR] 503 Service Unavailable (SERVICE_UNAVAILABLE) server error (shares)"
+        );
+
+        let err = client.get_share("vaccine_share").await.unwrap_err();
+        assert!(err.is_retriable());
+        assert_eq!(
+            err.to_string(),
+            "[CLIENT_ERROR] 429 Too Many Requests (TOO_MANY_REQUESTS) request not handled by server (share vaccine_share)"
+        );
+    }
+
+    #[tokio::test]
+    async fn classify_connection_failure() {
+        let profile =
+            Profile::new_bearer_token(1, "http://127.0.0.1:1/delta-sharing/", "token", None)
+                .unwrap();
+        let client = DeltaSharingClient::new(profile).unwrap();
+
+        let err = client.ping().await.unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::Transport { timeout: false }
+        ));
+        assert!(err.is_retriable());
+        assert_eq!(
+            err.to_string(),
+            "[TRANSPORT_ERROR] failed to connect to sharing server"
+        );
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.downcast_ref::<reqwest::Error>().is_some());
+    }
+
+    #[tokio::test]
+    async fn reject_redirect_of_api_request() {
+        let server = MockServer::start(|req| match req.target.as_str() {
+            "/shares" => MockResponse::new(302, "").with_header("Location", "/elsewhere"),
+            _ => MockResponse::ok(r#"{"items":[]}"#),
+        })
+        .await;
+
+        let err = server.client().list_shares().await.unwrap_err();
+        assert_eq!(err.message(), "unknown server response");
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn strip_token_on_cross_origin_redirect() {
+        let other = MockServer::start(|_| MockResponse::ok(r#"{"items":[]}"#)).await;
+        let location = other.url().join("shares").unwrap().to_string();
+        let server = MockServer::start(move |req| match req.target.as_str() {
+            "/shares" => MockResponse::new(302, "").with_header("Location", "/moved"),
+            "/moved" => MockResponse::new(302, "").with_header("Location", &location),
+            _ => MockResponse::new(404, ""),
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_follow_redirects(5)
+            .build()
+            .unwrap();
+
+        client.list_shares().await.unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].header("Authorization"), Some("Bearer token"));
+        assert_eq!(other.request_count(), 1);
+        assert_eq!(other.requests()[0].header("Authorization"), None);
+
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_default_header("X-Tenant", "acme")
+            .with_follow_redirects(5)
+            .build()
+            .unwrap();
+        assert!(client.list_shares().await.is_err());
+        assert_eq!(server.request_count(), 4);
+        assert_eq!(other.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn stream_pages_of_changes_query() {
+        let server = MockServer::start(|req| {
+            let mut body = String::from(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+                "\n",
+            ));
+            if req.target.contains("pageToken=page-2") {
+                body.push_str(r#"{"remove":{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{},"size":573,"version":2,"timestamp":1652140800000}}"#);
+            } else {
+                body.push_str(r#"{"add":{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{},"size":573,"version":1,"timestamp":1652140800000}}"#);
+                body.push_str("\n");
+                body.push_str(r#"{"endStreamAction":{"nextPageToken":"page-2"}}"#);
+            }
+            MockResponse::ok(body).with_header(HEADER_TABLE_VERSION, "2")
+        })
+        .await;
+        let client = server.client();
+        let query = TableChangesQuery::new(VersionRange::new_number(1, Some(2))).with_max_files(1);
+
+        let actions = client
+            .get_table_changes_stream("share", "schema", "table", &query)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(actions.len(), 4);
+        assert!(actions[0].is_protocol());
+        assert!(actions[1].is_metadata());
+        assert!(actions[2..].iter().all(TableAction::is_file));
+
+        let targets = server
+            .requests()
+            .into_iter()
+            .map(|req| req.target)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            vec![
+                "/shares/share/schemas/schema/tables/table/changes?startingVersion=1&endingVersion=2&maxFiles=1",
+                "/shares/share/schemas/schema/tables/table/changes?startingVersion=1&endingVersion=2&maxFiles=1&pageToken=page-2",
+            ]
+        );
+
+        let changes = client
+            .get_table_changes("share", "schema", "table", &query)
+            .await
+            .unwrap();
+        assert_eq!(changes.files().len(), 2);
+        assert_eq!(server.request_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn reject_predicate_on_unknown_column() {
+        let server = MockServer::start(|_| {
+            MockResponse::ok(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{\"type\":\"struct\",\"fields\":[{\"name\":\"date\",\"type\":\"date\",\"nullable\":true,\"metadata\":{}},{\"name\":\"event.date\",\"type\":\"date\",\"nullable\":true,\"metadata\":{}}]}","partitionColumns":["date"]}}"#,
+            ))
+            .with_header(HEADER_TABLE_VERSION, "1")
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_predicate_validation(true)
+            .build()
+            .unwrap();
+
+        let query = TableDataQuery::default().with_partition_filter("country", "NL");
+        let err = client
+            .get_table_data("share", "schema", "table", &query)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[REQUEST_ERROR] predicate on `country`, which is not a column of the table"
+        );
+        assert_eq!(
+            server.requests()[0].target,
+            "/shares/share/schemas/schema/tables/table/metadata"
+        );
+        assert_eq!(server.request_count(), 1);
+
+        let query = TableDataQuery::default().with_partition_filter("date", "2021-04-28");
+        client
+            .get_table_data("share", "schema", "table", &query)
+            .await
+            .unwrap();
+        assert_eq!(server.request_count(), 3);
+
+        let query = TableDataQuery::default().with_partition_filter("event.date", "2021-04-28");
+        client
+            .get_table_data("share", "schema", "table", &query)
+            .await
+            .unwrap();
+        assert_eq!(server.request_count(), 5);
+    }
+
+    #[tokio::test]
+    async fn rewrite_file_urls_of_changes() {
+        let server = MockServer::start(|_| {
+            MockResponse::ok(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+                "\n",
+                r#"{"add":{"url":"https://internal.example.com/part-0.parquet","id":"a0","partitionValues":{},"size":573,"version":1}}"#,
+                "\n",
+                r#"{"cdf":{"url":"https://internal.example.com/cdc-0.parquet","id":"c0","partitionValues":{},"size":573,"version":2}}"#,
+                "\n",
+                r#"{"remove":{"url":"https://internal.example.com/part-0.parquet","id":"a0","partitionValues":{},"size":573,"version":2}}"#,
+                "\n",
+            ))
+            .with_header(HEADER_TABLE_VERSION, "2")
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_url_rewrite(|mut url| {
+                url.set_host(Some("public.example.com")).unwrap();
+                url
+            })
+            .build()
+            .unwrap();
+        let query = TableChangesQuery::new(VersionRange::new_number(1, Some(2)));
+        let urls = |files: &[TableAction]| {
+            files
+                .iter()
+                .map(|f| f.as_file().unwrap().url().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            "https://public.example.com/part-0.parquet",
+            "https://public.example.com/cdc-0.parquet",
+            "https://public.example.com/part-0.parquet",
+        ];
+
+        let changes = client
+            .get_table_changes_raw("share", "schema", "table", &query)
+            .await
+            .unwrap();
+        assert_eq!(urls(changes.files()), expected);
+
+        let actions = client
+            .get_table_changes_stream("share", "schema", "table", &query)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(urls(&actions[2..]), expected);
+    }
+
+    #[tokio::test]
+    async fn changes_not_implemented() {
+        let server = MockServer::start(|_| MockResponse::new(501, "")).await;
+        let query = TableChangesQuery::new(VersionRange::new_number(1, None));
+
+        let err = server
+            .client()
+            .get_table_changes("share", "schema", "table", &query)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[SERVER_ERROR] 501 Not Implemented (NOT_IMPLEMENTED) endpoint not implemented by server (table share.schema.table)"
+        );
+        assert!(!err.is_retriable());
+    }
+
+    #[tokio::test]
+    async fn fall_back_to_listing_shares() {
+        let server = MockServer::start(|req| match req.target.as_str() {
+            "/shares" => MockResponse::ok(r#"{"items":[{"name":"vaccine_share"}]}"#),
+            "/shares/missing_share" => MockResponse::new(
+                404,
+                r#"{"errorCode":"RESOURCE_DOES_NOT_EXIST","message":"share not found"}"#,
+            ),
+            "/shares/legacy_share" => MockResponse::new(501, ""),
+            _ => MockResponse::new(404, "<html>Not Found</html>"),
+        })
+        .await;
+
+        let err = server
+            .client()
+            .get_share("vaccine_share")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[CLIENT_ERROR] 404 Not Found (ENDPOINT_NOT_FOUND) endpoint not found (share vaccine_share)"
+        );
+        assert_eq!(server.request_count(), 1);
+
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_get_share_fallback(true)
+            .build()
+            .unwrap();
+        let share = client.get_share("vaccine_share").await.unwrap().unwrap();
+        assert_eq!(share.name(), "vaccine_share");
+        assert!(client.get_share("legacy_share").await.unwrap().is_none());
+        assert_eq!(server.request_count(), 5);
+
+        assert!(client.get_share("missing_share").await.unwrap().is_none());
+        assert_eq!(server.request_count(), 6);
+
+        // Only `get_share` treats a 404 without error response as a missing
+        // endpoint.
+        let err = client.get_share_raw("vaccine_share").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[PARSE_RESPONSE_ERROR] failed to parse server response"
+        );
+        let err = client.list_schemas("vaccine_share").await.unwrap_err();
+        assert!(!err.is_not_found());
+        assert_eq!(
+            err.to_string(),
+            "[PARSE_RESPONSE_ERROR] failed to parse server response"
+        );
+    }
+
+    #[tokio::test]
+    async fn sort_files_by_version_and_id() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let server = MockServer::start(move |_| {
+            let mut files = vec![
+                r#"{"file":{"url":"https://example.com/c.parquet","id":"c","partitionValues":{},"size":1,"version":2}}"#,
+                r#"{"file":{"url":"https://example.com/b.parquet","id":"b","partitionValues":{},"size":1,"version":1}}"#,
+                r#"{"file":{"url":"https://example.com/a.parquet","id":"a","partitionValues":{},"size":1,"version":2}}"#,
+            ];
+            // Every other response lists the files in reverse order.
+            if counter.fetch_add(1, Ordering::SeqCst) % 2 == 1 {
+                files.reverse();
+            }
+            let mut body = String::from(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+            ));
+            for file in files {
+                body.push('\n');
+                body.push_str(file);
+            }
+            MockResponse::ok(body).with_header(HEADER_TABLE_VERSION, "2")
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_sorted_files(true)
+            .build()
+            .unwrap();
+        let query = TableDataQuery::default();
+
+        let mut fetches = vec![];
+        for _ in 0..2 {
+            let data = client
+                .get_table_data("share", "schema", "table", &query)
+                .await
+                .unwrap();
+            let ids = data
+                .files()
+                .iter()
+                .filter_map(TableAction::as_file)
+                .map(|file| file.id().to_owned())
+                .collect::<Vec<_>>();
+            fetches.push(ids);
+        }
+        assert_eq!(fetches[0], vec!["b", "a", "c"]);
+        assert_eq!(fetches[0], fetches[1]);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn negotiate_response_format() {
+        let delta_server = MockServer::start(|_| {
+            MockResponse::ok(concat!(
+                r#"{"protocol":{"deltaProtocol":{"minReaderVersion":1,"minWriterVersion":2}}}"#,
+                "\n",
+                r#"{"metaData":{"deltaMetadata":{"id":"f8d5c169","format":{"provider":"parquet","options":{}},"schemaString":"{}","partitionColumns":[],"configuration":{}}}}"#,
+                "\n",
+                r#"{"file":{"id":"c1","deltaSingleAction":{"add":{"path":"https://example.com/part-0.parquet","partitionValues":{},"size":573,"modificationTime":1619591469000,"dataChange":true}}}}"#,
+            ))
+            .with_header(HEADER_TABLE_VERSION, "1")
+            .with_header(HEADER_CAPABILITIES, "responseformat=delta")
+        })
+        .await;
+        let parquet_server = MockServer::start(|_| {
+            MockResponse::ok(concat!(
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                "\n",
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+                "\n",
+                r#"{"file":{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{},"size":573}}"#,
+            ))
+            .with_header(HEADER_TABLE_VERSION, "1")
+        })
+        .await;
+
+        let query = TableDataQuery::default();
+        let client = DeltaSharingClient::builder(delta_server.profile())
+            .with_response_format(ResponseFormat::Auto)
+            .build()
+            .unwrap();
+        let data = client
+            .get_table_data("share", "schema", "table", &query)
+            .await
+            .unwrap();
+        assert!(matches!(data.protocol(), TableAction::Delta(_)));
+        assert!(matches!(data.files()[0], TableAction::Delta(_)));
+        assert_eq!(
+            delta_server.requests()[0].header(HEADER_CAPABILITIES),
+            Some("responseformat=delta,parquet")
+        );
+
+        let client = DeltaSharingClient::builder(parquet_server.profile())
+            .with_response_format(ResponseFormat::Auto)
+            .build()
+            .unwrap();
+        let data = client
+            .get_table_data("share", "schema", "table", &query)
+            .await
+            .unwrap();
+        assert!(matches!(data.protocol(), TableAction::Parquet(_)));
+        assert!(matches!(data.files()[0], TableAction::Parquet(_)));
+        assert_eq!(
+            parquet_server.requests()[0].header(HEADER_CAPABILITIES),
+            Some("responseformat=delta,parquet")
+        );
+    }
+
+    #[tokio::test]
+    async fn cap_results_of_endless_listing() {
+        let server = MockServer::start(|req| {
+            let page = req.target.len();
+            MockResponse::ok(format!(
+                r#"{{"items":[{{"name":"a"}},{{"name":"b"}}],"nextPageToken":"page-{page}"}}"#
+            ))
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_max_list_results(5)
+            .build()
+            .unwrap();
+
+        let err = client.list_shares().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[PARSE_RESPONSE_ERROR] listing returned more than 5 results"
+        );
+        assert!(!err.is_retriable());
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn apply_default_page_size() {
+        let server =
+            MockServer::start(|_| MockResponse::ok(r#"{"items":[{"name":"a","share":"a"}]}"#))
+                .await;
+
+        server.client().list_shares().await.unwrap();
+        assert_eq!(server.requests()[0].target, "/shares");
+
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_default_page_size(25)
+            .build()
+            .unwrap();
+        client.list_shares().await.unwrap();
+        assert_eq!(server.requests()[1].target, "/shares?maxResults=25");
+
+        client.list_shares_with_page_size(10).await.unwrap();
+        assert_eq!(server.requests()[2].target, "/shares?maxResults=10");
+        client.list_schemas_with_page_size("a", 10).await.unwrap();
+        assert_eq!(
+            server.requests()[3].target,
+            "/shares/a/schemas?maxResults=10"
+        );
+    }
+
+    #[tokio::test]
+    async fn trip_and_reset_circuit_breaker() {
+        let failures = Arc::new(AtomicUsize::new(2));
+        let remaining = failures.clone();
+        let server = MockServer::start(move |_| {
+            let failing = remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if failing {
+                MockResponse::new(500, r#"{"errorCode":"INTERNAL_ERROR","message":"down"}"#)
+            } else {
+                MockResponse::ok(r#"{"items":[]}"#)
+            }
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_circuit_breaker(2, Duration::from_secs(60), Duration::from_secs(30))
+            .build()
+            .unwrap();
+
+        assert!(client.list_shares().await.is_err());
+        assert!(client.list_shares().await.is_err());
+        let err = client.list_shares().await.unwrap_err();
+        assert_eq!(
+            err.message(),
+            "circuit breaker is open after repeated failures"
+        );
+        assert_eq!(server.request_count(), 2);
+
+        let breaker = client.circuit_breaker.as_ref().unwrap();
+        breaker.advance(Duration::from_secs(29));
+        assert!(client.list_shares().await.is_err());
+        assert_eq!(server.request_count(), 2);
+
+        breaker.advance(Duration::from_secs(1));
+        assert!(client.list_shares().await.unwrap().is_empty());
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(failures.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn list_tables_populates_schema_for_both_endpoints() {
+        let server = MockServer::start(|req| match req.target.split('?').next().unwrap() {
+            "/shares/s1/schemas/all-tables" => MockResponse::ok(
+                r#"{"items":[{"name":"t1","schema":"d1","share":"s1","shareId":"sid","id":"tid1"},{"name":"t2","schema":"d2","share":"s1","shareId":"sid","id":"tid2"}]}"#,
+            ),
+            "/shares/s1/schemas/d1/tables" => MockResponse::ok(
+                r#"{"items":[{"name":"t1","schema":"d1","share":"s1","shareId":"sid","id":"tid1"}]}"#,
+            ),
+            _ => MockResponse::new(404, r#"{"errorCode":"NOT_FOUND","message":"not found"}"#),
+        })
+        .await;
+        let client = server.client();
+
+        let tables = client.list_tables_in_share("s1").await.unwrap();
+        let schemas = tables.iter().map(|t| t.schema()).collect::<Vec<_>>();
+        assert_eq!(schemas, vec!["d1", "d2"]);
+        assert!(tables.iter().all(|t| t.share_id() == Some("sid")));
+        assert_eq!(tables[1].id(), Some("tid2"));
+
+        let tables = client.list_tables_in_schema("s1", "d1").await.unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].schema(), "d1");
+        assert_eq!(tables[0].share_id(), Some("sid"));
+        assert_eq!(tables[0].id(), Some("tid1"));
+    }
+
+    #[tokio::test]
+    async fn strict_parsing_rejects_unknown_fields() {
+        let server = MockServer::start(|_| {
+            MockResponse::ok(r#"{"items":[{"name":"s1","owner":"someone"}]}"#)
+        })
+        .await;
+
+        let shares = server.client().list_shares().await.unwrap();
+        assert_eq!(shares[0].name(), "s1");
+
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_strict_parsing(true)
+            .build()
+            .unwrap();
+        let err = client.list_shares().await.unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unknown field `items.owner` in server response"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_large_table_data() {
+        let files = 10_000;
+        let mut body = String::from("{\"protocol\":{\"minReaderVersion\":1}}\n");
+        body.push_str(r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#);
+        body.push('\n');
+        for i in 0..files {
+            body.push_str(&format!(
+                r#"{{"file":{{"url":"https://example.com/part-{i}.parquet","id":"f{i}","partitionValues":{{}},"size":573}}}}"#
+            ));
+            body.push('\n');
+        }
+        let server = MockServer::start(move |_| {
+            MockResponse::ok(body.clone()).with_header(HEADER_TABLE_VERSION, "7")
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_max_line_size(1024)
+            .build()
+            .unwrap();
+
+        let stream = client
+            .get_table_data_stream("share", "schema", "table", &TableDataQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(stream.version(), 7);
+        assert!(stream.metadata().is_metadata());
+
+        let actions = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(actions.len(), files);
+        assert_eq!(actions[0].as_file().unwrap().id(), "f0");
+        assert_eq!(actions[files - 1].as_file().unwrap().id(), "f9999");
+    }
+
+    #[tokio::test]
+    async fn stream_pages_of_table_data() {
+        let server = MockServer::start(|req| {
+            let (file, end_stream) = if req.body.contains(r#""pageToken":"page-2""#) {
+                ("f2", r#"{"endStreamAction":{}}"#)
+            } else {
+                ("f1", r#"{"endStreamAction":{"nextPageToken":"page-2"}}"#)
+            };
+            MockResponse::ok(format!(
+                "{}\n{}\n{}\n{}\n",
+                r#"{"protocol":{"minReaderVersion":1}}"#,
+                r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+                format!(r#"{{"file":{{"url":"https://example.com/{file}.parquet","id":"{file}","partitionValues":{{}},"size":573}}}}"#),
+                end_stream,
+            ))
+            .with_header(HEADER_TABLE_VERSION, "7")
+        })
+        .await;
+        let client = server.client();
+        let query = TableDataQuery::default().with_max_files(1);
+
+        let stream = client
+            .get_table_data_stream("share", "schema", "table", &query)
+            .await
+            .unwrap();
+        let ids = stream
+            .map_ok(|action| action.as_file().unwrap().id().to_owned())
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(ids, vec!["f1", "f2"]);
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn stream_table_data_with_bom_and_crlf() {
+        let body = concat!(
+            "\u{feff}",
+            r#"{"protocol":{"minReaderVersion":1}}"#,
+            "\r\n",
+            r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{}","partitionColumns":[]}}"#,
+            "\r\n\r\n",
+            r#"{"file":{"url":"https://example.com/part-0.parquet","id":"f0","partitionValues":{},"size":573}}"#,
+            "\r\n",
+            r#"{"file":{"url":"https://example.com/part-1.parquet","id":"f1","partitionValues":{},"size":573}}"#,
+            "\r\n",
+        );
+        let server = MockServer::start(move |_| {
+            MockResponse::ok(body).with_header(HEADER_TABLE_VERSION, "7")
+        })
+        .await;
+        let client = server.client();
+
+        let stream = client
+            .get_table_data_stream("share", "schema", "table", &TableDataQuery::default())
+            .await
+            .unwrap();
+        assert!(stream.metadata().is_metadata());
+        let actions = stream.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[1].as_file().unwrap().id(), "f1");
+
+        let data = client
+            .get_table_data("share", "schema", "table", &TableDataQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(data.files().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_table_data_as_raw_bytes() {
+        let body = "{\"protocol\":{\"minReaderVersion\":1}}\n{\"unexpected\":true}\n";
+        let server = MockServer::start(move |_| MockResponse::ok(body)).await;
+
+        let bytes = server
+            .client()
+            .get_table_data_raw_bytes("share", "schema", "table", &TableDataQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(bytes, body.as_bytes());
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            requests[0].target,
+            "/shares/share/schemas/schema/tables/table/query"
+        );
+    }
+
+    #[test]
+    fn client_futures_are_send() {
+        fn assert_send<T: Send>(_: T) {}
+
+        let client = DeltaSharingClient::new(profile()).unwrap();
+        assert_send(client.list_shares());
+        assert_send(client.get_table_data("share", "schema", "table", &TableDataQuery::default()));
+    }
+
+    #[tokio::test]
+    async fn detect_and_cache_server_capabilities() {
+        let server = MockServer::start(|_| {
+            MockResponse::ok(r#"{"items":[]}"#)
+                .with_header(HEADER_CAPABILITIES, "responseformat=parquet")
+        })
+        .await;
+        let client = server.client();
+
+        let capabilities = client.server_capabilities().await.unwrap();
+        assert!(capabilities.supports_response_format("parquet"));
+        let err = capabilities.require_response_format("delta").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[REQUEST_ERROR] feature not supported by this server: responseformat=delta"
+        );
+
+        assert_eq!(client.server_capabilities().await.unwrap(), capabilities);
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn reject_delta_format_queries_unsupported_by_server() {
+        let server = MockServer::start(|_| {
+            MockResponse::ok(r#"{"items":[]}"#)
+                .with_header(HEADER_CAPABILITIES, "responseformat=parquet")
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_response_format(ResponseFormat::Delta)
+            .build()
+            .unwrap();
+
+        let unsupported =
+            "[REQUEST_ERROR] feature not supported by this server: responseformat=delta";
+        let query = TableChangesQuery::new(VersionRange::new_number(1, None));
+        let err = client
+            .get_table_changes("share", "schema", "table", &query)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), unsupported);
+
+        let changes = client
+            .get_table_changes_stream("share", "schema", "table", &query)
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(&changes[..], [Err(e)] if e.to_string() == unsupported));
+
+        let err = client
+            .get_table_data("share", "schema", "table", &TableDataQuery::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), unsupported);
+
+        let err = client
+            .get_table_metadata("share", "schema", "table")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), unsupported);
+
+        // Only the capabilities were requested, none of the queries was sent.
+        assert_eq!(server.request_count(), 1);
+        assert_eq!(server.requests()[0].target, "/shares?maxResults=1");
+    }
+
+    #[tokio::test]
+    async fn send_delta_format_queries_supported_by_server() {
+        let server = MockServer::start(|req| match req.target.as_str() {
+            "/shares?maxResults=1" => MockResponse::ok(r#"{"items":[]}"#)
+                .with_header(HEADER_CAPABILITIES, "responseformat=delta,parquet"),
+            _ => MockResponse::new(501, ""),
+        })
+        .await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_response_format(ResponseFormat::Delta)
+            .build()
+            .unwrap();
+
+        let query = TableChangesQuery::new(VersionRange::new_number(1, None));
+        let err = client
+            .get_table_changes("share", "schema", "table", &query)
+            .await
+            .unwrap_err();
+        assert!(err.message().contains("endpoint not implemented by server"));
+        assert_eq!(server.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn resolve_relative_table_version() {
+        let server =
+            MockServer::start(|_| MockResponse::ok("").with_header(HEADER_TABLE_VERSION, "5"))
+                .await;
+        let client = server.client();
+
+        let version = client
+            .get_table_version("share", "schema", "table", &TableVersionQuery::Relative(-1))
+            .await
+            .unwrap();
+        assert_eq!(version.version(), 4);
+        assert_eq!(server.request_count(), 1);
+        assert_eq!(
+            server.requests()[0].target,
+            "/shares/share/schemas/schema/tables/table/version"
+        );
+
+        let version = client
+            .get_table_version(
+                "share",
+                "schema",
+                "table",
+                &TableVersionQuery::Relative(-10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(version.version(), 0);
+    }
+
+    #[derive(Debug)]
+    struct PathSigner;
+
+    impl RequestSigner for PathSigner {
+        fn sign(&self, request: &mut reqwest::Request) -> Result<()> {
+            let signature = format!("signed:{}", request.url().path());
+            request
+                .headers_mut()
+                .insert("X-Signature", signature.parse().unwrap());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn sign_outgoing_requests() {
+        let server = MockServer::start(|_| MockResponse::ok(r#"{"items":[]}"#)).await;
+        let client = DeltaSharingClient::builder(server.profile())
+            .with_request_signer(Arc::new(PathSigner))
+            .build()
+            .unwrap();
+
+        client.list_shares().await.unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(request.header("X-Signature"), Some("signed:/shares"));
+        assert_eq!(request.header("Authorization"), Some("Bearer token"));
+    }
+
+    #[tokio::test]
+    async fn build_catalog_tree() {
+        let server = MockServer::start(|req| match req.target.split('?').next().unwrap() {
+            "/shares" => MockResponse::ok(r#"{"items":[{"name":"s1"},{"name":"s2"}]}"#),
+            "/shares/s1/schemas" => MockResponse::ok(
+                r#"{"items":[{"name":"d1","share":"s1"},{"name":"d2","share":"s1"}]}"#,
+            ),
+            "/shares/s2/schemas" => MockResponse::ok(r#"{"items":[]}"#),
+            "/shares/s1/schemas/d1/tables" => MockResponse::ok(
+                r#"{"items":[{"name":"t1","schema":"d1","share":"s1"},{"name":"t2","schema":"d1","share":"s1"}]}"#,
+            ),
+            "/shares/s1/schemas/d2/tables" => MockResponse::ok(
+                r#"{"items":[{"name":"t3","schema":"d2","share":"s1"}]}"#,
+            ),
+            _ => MockResponse::new(404, r#"{"errorCode":"NOT_FOUND","message":"not found"}"#),
+        })
+        .await;
+
+        let tree = server.client().catalog_tree().await.unwrap();
+        assert_eq!(server.request_count(), 5);
+
+        let summary = tree
+            .iter()
+            .map(|share| {
+                let schemas = share
+                    .schemas
+                    .iter()
+                    .map(|schema| {
+                        let tables = schema.tables.iter().map(|t| t.name()).collect::<Vec<_>>();
+                        (schema.schema.name(), tables)
+                    })
+                    .collect::<Vec<_>>();
+                (share.share.name(), schemas)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("s1", vec![("d1", vec!["t1", "t2"]), ("d2", vec!["t3"])]),
+                ("s2", vec![]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn authorize_bearer_token_request() {
+        let request = reqwest::Client::new()
+            .get("https://example.com")
+            .authorize(&profile(), None)
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let auth_header = request.headers().get(http::header::AUTHORIZATION).unwrap();
+        assert_eq!(auth_header, "Bearer token");
+    }
+
+    #[derive(Debug)]
+    struct LeakingTokenProvider;
+
+    impl TokenProvider for LeakingTokenProvider {
+        async fn provide_token(&self) -> Result<&str> {
+            Err(DeltaSharingError::profile(
+                "token secret-token was rejected",
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn redact_token_in_authorization_error() {
+        let profile = Profile::new_bearer_token(
+            1,
+            "https://sharing.delta.io/delta-sharing/",
+            "secret-token",
+            None,
+        )
+        .unwrap();
+        let client = DeltaSharingClient::new(profile.clone()).unwrap();
+
+        let err = reqwest::Client::new()
+            .get("https://example.com")
+            .authorize(&profile, Some(&LeakingTokenProvider))
+            .await
+            .map_err(|e| client.authorization_error(e))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[PROFILE_ERROR] failed to authorize request. Reason: [PROFILE_ERROR] token ******** was rejected"
+        );
+        assert_eq!(
+            format!("{err:?}"),
+            r#"DeltaSharingError { kind: Profile, message: "failed to authorize request. Reason: [PROFILE_ERROR] token ******** was rejected", source: None }"#
+        );
+    }
+
+    #[derive(Debug)]
+    struct StaticTokenProvider(&'static str);
+
+    impl TokenProvider for StaticTokenProvider {
+        async fn provide_token(&self) -> Result<&str> {
+            Ok(self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn authorize_with_injected_token_provider() {
+        let endpoint = "https://sharing.delta.io/delta-sharing/".parse().unwrap();
+        let external = Profile::from_profile_type(1, endpoint, ProfileType::External);
+        let provider = StaticTokenProvider("injected");
+
+        let err = reqwest::Client::new()
+            .get("https://example.com")
+            .authorize(&external, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.message(), "Profile requires an external token provider");
+
+        let request = reqwest::Client::new()
+            .get("https://example.com")
+            .authorize(&external, Some(&provider))
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        let auth_header = request.headers().get(http::header::AUTHORIZATION).unwrap();
+        assert_eq!(auth_header, "Bearer injected");
+
+        let request = reqwest::Client::new()
+            .get("https://example.com")
+            .authorize(&profile(), Some(&provider))
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        let auth_header = request.headers().get(http::header::AUTHORIZATION).unwrap();
+        assert_eq!(auth_header, "Bearer injected");
+    }
+
+    #[tokio::test]
+    async fn authorize_anonymous_request() {
+        let endpoint = "https://sharing.delta.io/delta-sharing/".parse().unwrap();
+        let request = reqwest::Client::new()
+            .get("https://example.com")
+            .authorize(&Profile::anonymous(endpoint), None)
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(request.headers().get(http::header::AUTHORIZATION).is_none());
     }
 }
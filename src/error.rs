@@ -7,20 +7,47 @@ pub enum ErrorKind {
     Internal,
     Profile,
     Request,
-    ClientError { status: StatusCode, code: String },
-    ServerError { status: StatusCode, code: String },
+    ClientError {
+        status: StatusCode,
+        code: String,
+    },
+    ServerError {
+        status: StatusCode,
+        code: String,
+    },
     ParseResponse,
+    /// Failure to download a file from its presigned URL. The status is the
+    /// response status of the object store, if it responded at all.
+    Download {
+        status: Option<StatusCode>,
+    },
+    /// Failure to exchange a request and response with the sharing server,
+    /// e.g. because the connection could not be established or timed out.
+    Transport {
+        timeout: bool,
+    },
 }
 
 #[derive(Debug)]
 pub struct DeltaSharingError {
     kind: ErrorKind,
     message: String,
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
 
 impl DeltaSharingError {
     pub fn new(kind: ErrorKind, message: String) -> Self {
-        Self { kind, message }
+        Self {
+            kind,
+            message,
+            source: None,
+        }
+    }
+
+    /// Attach the underlying error, returned by [`std::error::Error::source`].
+    pub fn with_source(mut self, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        self.source = Some(Box::new(source));
+        self
     }
 
     pub fn message(&self) -> &str {
@@ -41,6 +68,36 @@ impl DeltaSharingError {
         )
     }
 
+    /// Check whether the request was rejected because of its credentials.
+    ///
+    /// This is the case for client errors with status 401 (unauthorized) or
+    /// 403 (forbidden), which usually mean the credentials need refreshing.
+    pub fn is_auth_error(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::ClientError {
+                status: StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN,
+                ..
+            }
+        )
+    }
+
+    /// Check whether the failed request may succeed when retried.
+    ///
+    /// Server errors, throttling and timeouts are considered retriable, as
+    /// are requests that failed without a response from the sharing server
+    /// or the object store. Endpoints the server does not implement (501) are
+    /// not.
+    pub fn is_retriable(&self) -> bool {
+        match &self.kind {
+            ErrorKind::ServerError { status, .. } => *status != StatusCode::NOT_IMPLEMENTED,
+            ErrorKind::ClientError { status, .. } => is_retriable_status(*status),
+            ErrorKind::Download { status } => status.map_or(true, is_retriable_status),
+            ErrorKind::Transport { .. } => true,
+            _ => false,
+        }
+    }
+
     pub fn internal(message: impl Into<String>) -> Self {
         Self::new(ErrorKind::Internal, message.into())
     }
@@ -76,23 +133,255 @@ impl DeltaSharingError {
     pub fn parse_response(message: impl Into<String>) -> Self {
         Self::new(ErrorKind::ParseResponse, message.into())
     }
+
+    pub fn download(status: Option<StatusCode>, message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Download { status }, message.into())
+    }
+
+    pub fn transport(timeout: bool, message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Transport { timeout }, message.into())
+    }
+
+    /// Check whether the error occurred while downloading a file from the
+    /// object store, as opposed to a request to the sharing server.
+    pub fn is_download_error(&self) -> bool {
+        matches!(self.kind, ErrorKind::Download { .. })
+    }
 }
 
+/// Errors are displayed as `[KIND] message`. Errors returned by the server
+/// include the response status and error code after the kind, i.e.
+/// `[CLIENT_ERROR] 404 Not Found (RESOURCE_DOES_NOT_EXIST) message`.
+/// Download errors include the status of the object store, if any.
 impl fmt::Display for DeltaSharingError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.kind {
             ErrorKind::ClientError { status, code } => {
-                write!(f, "Client error: {} - {} - {}", status, code, self.message)
+                write!(f, "[CLIENT_ERROR] {} ({}) {}", status, code, self.message)
             }
             ErrorKind::ServerError { status, code } => {
-                write!(f, "Server error: {} - {} - {}", status, code, self.message)
+                write!(f, "[SERVER_ERROR] {} ({}) {}", status, code, self.message)
+            }
+            ErrorKind::Internal => write!(f, "[INTERNAL_ERROR] {}", self.message),
+            ErrorKind::Profile => write!(f, "[PROFILE_ERROR] {}", self.message),
+            ErrorKind::ParseResponse => write!(f, "[PARSE_RESPONSE_ERROR] {}", self.message),
+            ErrorKind::Request => write!(f, "[REQUEST_ERROR] {}", self.message),
+            ErrorKind::Download {
+                status: Some(status),
+            } => write!(f, "[DOWNLOAD_ERROR] {} {}", status, self.message),
+            ErrorKind::Download { status: None } => {
+                write!(f, "[DOWNLOAD_ERROR] {}", self.message)
             }
-            ErrorKind::Internal => todo!(),
-            ErrorKind::Profile => todo!(),
-            ErrorKind::ParseResponse => todo!(),
-            ErrorKind::Request => todo!(),
+            ErrorKind::Transport { .. } => write!(f, "[TRANSPORT_ERROR] {}", self.message),
         }
     }
 }
 
-impl std::error::Error for DeltaSharingError {}
+impl std::error::Error for DeltaSharingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<serde_json::Error> for DeltaSharingError {
+    fn from(err: serde_json::Error) -> Self {
+        tracing::error!(err = ?err, "failed to parse server response");
+        DeltaSharingError::parse_response("failed to parse server response").with_source(err)
+    }
+}
+
+impl From<url::ParseError> for DeltaSharingError {
+    fn from(err: url::ParseError) -> Self {
+        tracing::error!(err = ?err, "failed to construct URL");
+        DeltaSharingError::internal("failed to construct URL").with_source(err)
+    }
+}
+
+impl From<DeltaSharingError> for std::io::Error {
+    fn from(err: DeltaSharingError) -> Self {
+        use std::io::ErrorKind as IoErrorKind;
+
+        let kind = match &err.kind {
+            ErrorKind::ClientError { status, .. }
+            | ErrorKind::ServerError { status, .. }
+            | ErrorKind::Download {
+                status: Some(status),
+            } => match *status {
+                StatusCode::NOT_FOUND => IoErrorKind::NotFound,
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => IoErrorKind::PermissionDenied,
+                StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => IoErrorKind::TimedOut,
+                _ => IoErrorKind::Other,
+            },
+            ErrorKind::ParseResponse => IoErrorKind::InvalidData,
+            ErrorKind::Request | ErrorKind::Profile => IoErrorKind::InvalidInput,
+            ErrorKind::Transport { timeout: true } => IoErrorKind::TimedOut,
+            ErrorKind::Download { status: None }
+            | ErrorKind::Transport { timeout: false }
+            | ErrorKind::Internal => IoErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// Check whether a response status indicates a failure that may succeed when
+/// retried.
+pub(crate) fn is_retriable_status(status: StatusCode) -> bool {
+    (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// Placeholder for secrets in messages and debug output.
+pub(crate) const REDACTED: &str = "********";
+
+/// Replace every occurrence of `secret` in `message` with a placeholder.
+///
+/// Use this before putting anything derived from credentials into an error
+/// message or log line.
+pub(crate) fn redact(message: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        return message.to_owned();
+    }
+    message.replace(secret, REDACTED)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redact_secret() {
+        assert_eq!(
+            redact("token abc expired, abc!", "abc"),
+            "token ******** expired, ********!"
+        );
+        assert_eq!(redact("nothing to hide", "abc"), "nothing to hide");
+        assert_eq!(redact("empty secret", ""), "empty secret");
+    }
+
+    #[test]
+    fn auth_errors() {
+        let err = DeltaSharingError::client(StatusCode::UNAUTHORIZED, "ERR", "unauthorized");
+        assert!(err.is_auth_error());
+
+        let err = DeltaSharingError::client(StatusCode::FORBIDDEN, "ERR", "forbidden");
+        assert!(err.is_auth_error());
+
+        let err = DeltaSharingError::client(StatusCode::BAD_REQUEST, "ERR", "bad request");
+        assert!(!err.is_auth_error());
+    }
+
+    #[test]
+    fn retriable_errors() {
+        let err = DeltaSharingError::server(StatusCode::INTERNAL_SERVER_ERROR, "ERR", "boom");
+        assert!(err.is_retriable());
+
+        let err = DeltaSharingError::client(StatusCode::TOO_MANY_REQUESTS, "ERR", "slow down");
+        assert!(err.is_retriable());
+
+        let err = DeltaSharingError::client(StatusCode::NOT_FOUND, "ERR", "missing");
+        assert!(!err.is_retriable());
+
+        let err = DeltaSharingError::server(StatusCode::NOT_IMPLEMENTED, "ERR", "unsupported");
+        assert!(!err.is_retriable());
+
+        let err = DeltaSharingError::profile("expired");
+        assert!(!err.is_retriable());
+    }
+
+    #[test]
+    fn into_io_error() {
+        let err = DeltaSharingError::client(StatusCode::NOT_FOUND, "ERR", "missing");
+        let io_err = std::io::Error::from(err);
+        assert_eq!(io_err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(
+            io_err.to_string(),
+            "[CLIENT_ERROR] 404 Not Found (ERR) missing"
+        );
+
+        let err = DeltaSharingError::server(StatusCode::GATEWAY_TIMEOUT, "ERR", "slow");
+        assert_eq!(
+            std::io::Error::from(err).kind(),
+            std::io::ErrorKind::TimedOut
+        );
+
+        let err = DeltaSharingError::client(StatusCode::FORBIDDEN, "ERR", "forbidden");
+        assert_eq!(
+            std::io::Error::from(err).kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+
+        let err = DeltaSharingError::parse_response("invalid json");
+        let io_err = std::io::Error::from(err);
+        assert_eq!(io_err.kind(), std::io::ErrorKind::InvalidData);
+        let inner = io_err.into_inner().unwrap();
+        assert!(inner.downcast_ref::<DeltaSharingError>().is_some());
+    }
+
+    #[test]
+    fn display_format() {
+        let cases = [
+            (
+                DeltaSharingError::client(
+                    StatusCode::NOT_FOUND,
+                    "RESOURCE_DOES_NOT_EXIST",
+                    "missing",
+                ),
+                "[CLIENT_ERROR] 404 Not Found (RESOURCE_DOES_NOT_EXIST) missing",
+            ),
+            (
+                DeltaSharingError::server(StatusCode::BAD_GATEWAY, "INTERNAL_ERROR", "boom"),
+                "[SERVER_ERROR] 502 Bad Gateway (INTERNAL_ERROR) boom",
+            ),
+            (DeltaSharingError::internal("oops"), "[INTERNAL_ERROR] oops"),
+            (
+                DeltaSharingError::profile("expired"),
+                "[PROFILE_ERROR] expired",
+            ),
+            (
+                DeltaSharingError::parse_response("invalid json"),
+                "[PARSE_RESPONSE_ERROR] invalid json",
+            ),
+            (
+                DeltaSharingError::request("invalid url"),
+                "[REQUEST_ERROR] invalid url",
+            ),
+            (
+                DeltaSharingError::download(Some(StatusCode::FORBIDDEN), "expired"),
+                "[DOWNLOAD_ERROR] 403 Forbidden expired",
+            ),
+            (
+                DeltaSharingError::download(None, "connection reset"),
+                "[DOWNLOAD_ERROR] connection reset",
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn from_serde_json_error() {
+        let json_err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let err = DeltaSharingError::from(json_err);
+        assert!(matches!(err.kind(), ErrorKind::ParseResponse));
+        assert_eq!(err.message(), "failed to parse server response");
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+    }
+
+    #[test]
+    fn from_url_parse_error() {
+        let url_err = url::Url::parse("not a url").unwrap_err();
+        let err = DeltaSharingError::from(url_err);
+        assert!(matches!(err.kind(), ErrorKind::Internal));
+        assert_eq!(err.message(), "failed to construct URL");
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(
+            source.downcast_ref::<url::ParseError>(),
+            Some(&url::ParseError::RelativeUrlWithoutBase)
+        );
+    }
+}
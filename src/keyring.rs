@@ -0,0 +1,132 @@
+//! Bearer tokens stored in the keyring of the operating system.
+
+use std::fmt::Formatter;
+use std::sync::OnceLock;
+
+use ::keyring::Entry;
+
+use crate::error::REDACTED;
+use crate::profile::TokenProvider;
+use crate::{DeltaSharingError, Result};
+
+/// Token provider reading the bearer token from the keyring of the operating
+/// system, e.g. the macOS keychain or the Windows credential manager.
+///
+/// The token is stored as the password of the keyring entry identified by
+/// `service` and `account`. It is read on first use and cached afterwards.
+pub struct KeyringTokenProvider {
+    service: String,
+    account: String,
+    entry: Entry,
+    token: OnceLock<String>,
+}
+
+impl KeyringTokenProvider {
+    /// Create a token provider for the keyring entry identified by `service`
+    /// and `account`.
+    ///
+    /// # Example
+    /// ```no_run,rust
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::sync::Arc;
+    ///
+    /// use delta_sharing_client_rs::profile::KeyringTokenProvider;
+    /// use delta_sharing_client_rs::{DeltaSharingClient, Profile};
+    ///
+    /// let profile = Profile::try_from_path_with_external_token("./path/to/profile.json")?;
+    /// let provider = KeyringTokenProvider::new("delta-sharing", "alice")?;
+    /// let client = DeltaSharingClient::builder(profile)
+    ///     .with_token_provider(Arc::new(provider))
+    ///     .build()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn new(service: impl Into<String>, account: impl Into<String>) -> Result<Self> {
+        let service = service.into();
+        let account = account.into();
+        let entry =
+            Entry::new(&service, &account).map_err(|e| keyring_error(&service, &account, e))?;
+        Ok(Self {
+            service,
+            account,
+            entry,
+            token: OnceLock::new(),
+        })
+    }
+
+    /// Read the bearer token from the keyring, bypassing the cache.
+    pub(crate) fn read_token(&self) -> Result<String> {
+        self.entry
+            .get_password()
+            .map_err(|e| keyring_error(&self.service, &self.account, e))
+    }
+}
+
+impl TokenProvider for KeyringTokenProvider {
+    async fn provide_token(&self) -> Result<&str> {
+        if let Some(token) = self.token.get() {
+            return Ok(token);
+        }
+        let token = self.read_token()?;
+        Ok(self.token.get_or_init(|| token))
+    }
+}
+
+impl std::fmt::Debug for KeyringTokenProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyringTokenProvider")
+            .field("service", &self.service)
+            .field("account", &self.account)
+            .field("token", &self.token.get().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+fn keyring_error(service: &str, account: &str, err: ::keyring::Error) -> DeltaSharingError {
+    tracing::error!(err = ?err, service, account, "failed to read token from keyring");
+    match err {
+        ::keyring::Error::NoEntry => DeltaSharingError::profile(format!(
+            "No bearer token found in keyring for service `{service}` and account `{account}`"
+        )),
+        err => DeltaSharingError::profile(format!(
+            "Failed to read bearer token from keyring for service `{service}` and account `{account}`: {err}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Profile;
+
+    fn use_mock_keyring() {
+        ::keyring::set_default_credential_builder(::keyring::mock::default_credential_builder());
+    }
+
+    #[tokio::test]
+    async fn provide_token_from_keyring() {
+        use_mock_keyring();
+        let provider = KeyringTokenProvider::new("delta-sharing", "alice").unwrap();
+        provider.entry.set_password("keyring-token").unwrap();
+
+        assert_eq!(provider.provide_token().await.unwrap(), "keyring-token");
+
+        provider.entry.set_password("rotated-token").unwrap();
+        assert_eq!(provider.provide_token().await.unwrap(), "keyring-token");
+        assert_eq!(provider.read_token().unwrap(), "rotated-token");
+    }
+
+    #[test]
+    fn missing_keyring_entry() {
+        use_mock_keyring();
+        let err = Profile::with_keyring_token(
+            "https://sharing.delta.io/delta-sharing/",
+            "delta-sharing",
+            "bob",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[PROFILE_ERROR] No bearer token found in keyring for service `delta-sharing` and account `bob`"
+        );
+    }
+}
@@ -1,14 +1,42 @@
+//! Client for the Delta Sharing protocol.
+//!
+//! # Features
+//!
+//! | Feature | Default | Description |
+//! |---------|---------|-------------|
+//! | `serde` | yes     | Implement `Serialize` for request-side types that are only sent as query parameters, such as [`request::query::TableChangesQuery`]. |
+//! | `arrow` | no      | Convert table schemas into Arrow schemas. |
+//! | `keyring` | no    | Read bearer tokens from the keyring of the operating system, see [`Profile::with_keyring_token`]. |
+//!
+//! Response types and request bodies are always (de)serializable, as they
+//! are exchanged with the server as JSON.
+//!
+//! # Minimum supported Rust version
+//!
+//! The minimum supported Rust version is 1.75, which stabilized `async fn`
+//! and return-position `impl Trait` in traits as used by
+//! [`profile::TokenProvider`].
+
+#[cfg(feature = "arrow")]
+mod arrow;
+mod circuit_breaker;
 mod client;
 mod config;
 mod error;
+#[cfg(feature = "keyring")]
+mod keyring;
+pub mod observer;
 pub mod profile;
 pub mod request;
-mod response;
+pub mod response;
+pub mod signer;
+#[cfg(test)]
+mod test_util;
 
 pub use error::DeltaSharingError;
 
-pub use client::DeltaSharingClient;
-pub use profile::Profile;
+pub use client::{DeltaSharingClient, DeltaSharingClientBuilder, UrlRewrite};
+pub use profile::{Profile, ProfileBuilder};
 
 pub type Error = DeltaSharingError;
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,36 @@
+//! Hooks to observe the requests made by the client.
+//!
+//! Implement [`Observer`] to emit metrics such as request counts, latencies
+//! and downloaded bytes, and register it with
+//! [`DeltaSharingClientBuilder::with_observer`](crate::DeltaSharingClientBuilder::with_observer).
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+use http::StatusCode;
+
+/// Callbacks invoked by the client while making requests.
+///
+/// All callbacks default to doing nothing.
+pub trait Observer: Debug + Send + Sync {
+    /// Called right before a request is sent.
+    fn on_request_start(&self) {}
+
+    /// Called when the server responded to a request, with the status code
+    /// and the time until the response headers were received. Not called
+    /// when the request failed before a response was received.
+    fn on_request_end(&self, status: StatusCode, duration: Duration) {
+        let _ = (status, duration);
+    }
+
+    /// Called for every chunk of a response body that is read.
+    fn on_bytes_downloaded(&self, bytes: usize) {
+        let _ = bytes;
+    }
+}
+
+/// Observer that ignores all callbacks.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {}
@@ -1,12 +1,386 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
 use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::response::TableMetadata;
+use crate::{DeltaSharingError, Result};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[must_use = "queries do nothing unless passed to the client"]
 pub struct TableDataQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
     predicate_hints: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     json_predicate_hints: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     limit_hint: Option<u32>,
-    version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     timestamp: Option<String>,
-    starting_version: Option<u32>,
-    ending_version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    starting_version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ending_version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_files: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page_token: Option<String>,
+    #[serde(skip)]
+    partition_filters: BTreeMap<String, String>,
+}
+
+impl TableDataQuery {
+    /// Read the table at the given version.
+    ///
+    /// Cannot be combined with a timestamp or a version range.
+    pub fn with_version(mut self, version: u64) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Read the table at the version current at the given timestamp.
+    ///
+    /// Cannot be combined with a version or a version range.
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+        self
+    }
+
+    /// Read the files added from the given version onwards.
+    ///
+    /// Cannot be combined with a version or a timestamp.
+    pub fn with_starting_version(mut self, starting_version: u64) -> Self {
+        self.starting_version = Some(starting_version);
+        self
+    }
+
+    /// Read the files added up to and including the given version. Requires
+    /// a starting version.
+    pub fn with_ending_version(mut self, ending_version: u64) -> Self {
+        self.ending_version = Some(ending_version);
+        self
+    }
+
+    /// Check that the query reads either a single version of the table or a
+    /// range of versions, but not both.
+    ///
+    /// The client validates every query before sending it.
+    pub fn validate(&self) -> Result<()> {
+        let message = match (
+            self.version,
+            &self.timestamp,
+            self.starting_version,
+            self.ending_version,
+        ) {
+            (Some(_), Some(_), _, _) => "version and timestamp cannot both be set",
+            (Some(_), _, Some(_), _) | (Some(_), _, _, Some(_)) => {
+                "version cannot be combined with a starting or ending version"
+            }
+            (_, Some(_), Some(_), _) | (_, Some(_), _, Some(_)) => {
+                "timestamp cannot be combined with a starting or ending version"
+            }
+            (_, _, None, Some(_)) => "ending version requires a starting version",
+            (_, _, Some(start), Some(end)) if start > end => {
+                "starting version cannot be greater than ending version"
+            }
+            _ => return Ok(()),
+        };
+        tracing::error!(query = ?self, message, "invalid table data query");
+        Err(DeltaSharingError::request(format!(
+            "invalid table data query: {message}"
+        )))
+    }
+
+    /// Ask the server to return at most `max_files` files per page.
+    ///
+    /// The client follows the page tokens returned by the server and merges
+    /// the files of all pages.
+    pub fn with_max_files(mut self, max_files: u32) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    pub(crate) fn with_page_token(mut self, page_token: Option<&str>) -> Self {
+        self.page_token = page_token.map(str::to_owned);
+        self
+    }
+
+    /// Only request files whose partition `column` has the given `value`.
+    ///
+    /// The partition filters are sent as JSON predicate hints, combining
+    /// multiple filters with `and`. Values are compared as strings, i.e. in
+    /// the form they take in the partition values of the file actions. Like
+    /// all predicate hints the filters are best effort; the server may return
+    /// files that do not match.
+    pub fn with_partition_filter(
+        mut self,
+        column: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.partition_filters.insert(column.into(), value.into());
+        self.json_predicate_hints = Some(render_partition_filters(&self.partition_filters));
+        self
+    }
+
+    pub(crate) fn has_predicate_hints(&self) -> bool {
+        self.predicate_hints.is_some() || self.json_predicate_hints.is_some()
+    }
+
+    /// Retrieve the partition filters of the query
+    pub fn partition_filters(&self) -> &BTreeMap<String, String> {
+        &self.partition_filters
+    }
+
+    /// Check that every partition filter refers to a partition column of the
+    /// table.
+    pub fn validate_partition_filters(&self, metadata: &TableMetadata) -> Result<()> {
+        let partition_columns = metadata.partition_columns();
+        match self
+            .partition_filters
+            .keys()
+            .find(|column| !partition_columns.contains(column))
+        {
+            Some(column) => {
+                tracing::error!(column = %column, "partition filter on non-partition column");
+                Err(DeltaSharingError::request(format!(
+                    "partition filter on `{column}`, which is not a partition column of the table"
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Check that every column referenced by the JSON predicate hints of the
+    /// query, such as the partition filters, is a column of the table.
+    ///
+    /// A dotted name is accepted if it is a column of the table, or else if
+    /// its first segment is, as nested columns are checked by their top-level
+    /// field.
+    pub fn validate_predicate_columns(&self, metadata: &TableMetadata) -> Result<()> {
+        let Some(hints) = &self.json_predicate_hints else {
+            return Ok(());
+        };
+        let predicate = serde_json::from_str::<Value>(hints).map_err(|e| {
+            tracing::error!(err = ?e, "failed to parse JSON predicate hints");
+            DeltaSharingError::request("JSON predicate hints are not valid JSON")
+        })?;
+
+        let mut columns = vec![];
+        collect_predicate_columns(&predicate, &mut columns);
+        let schema = metadata.schema();
+        match columns.into_iter().find(|column| {
+            let top_level = column.split('.').next().unwrap_or(column);
+            schema.field(column).is_none() && schema.field(top_level).is_none()
+        }) {
+            Some(column) => {
+                tracing::error!(column, "predicate on unknown column");
+                Err(DeltaSharingError::request(format!(
+                    "predicate on `{column}`, which is not a column of the table"
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Collect the names of the columns referenced by a JSON predicate.
+fn collect_predicate_columns<'a>(predicate: &'a Value, columns: &mut Vec<&'a str>) {
+    if predicate["op"] == "column" {
+        if let Some(name) = predicate["name"].as_str() {
+            columns.push(name);
+        }
+    }
+    if let Some(children) = predicate["children"].as_array() {
+        for child in children {
+            collect_predicate_columns(child, columns);
+        }
+    }
+}
+
+fn render_partition_filters(filters: &BTreeMap<String, String>) -> String {
+    let mut predicates = filters
+        .iter()
+        .map(|(column, value)| {
+            json!({
+                "op": "equal",
+                "children": [
+                    {"op": "column", "name": column, "valueType": "string"},
+                    {"op": "literal", "value": value, "valueType": "string"},
+                ],
+            })
+        })
+        .collect::<Vec<Value>>();
+    let predicate = if predicates.len() == 1 {
+        predicates.remove(0)
+    } else {
+        json!({"op": "and", "children": predicates})
+    };
+    predicate.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::response::QueryTableMetadataResponse;
+
+    #[test]
+    fn serialize_minimal_table_data_query() {
+        let query = TableDataQuery::default();
+        assert_eq!(serde_json::to_value(&query).unwrap(), json!({}));
+    }
+
+    #[test]
+    fn serialize_table_data_query_camel_case() {
+        let query = TableDataQuery {
+            json_predicate_hints: Some(String::from("{}")),
+            limit_hint: Some(10),
+            starting_version: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({"jsonPredicateHints": "{}", "limitHint": 10, "startingVersion": 1})
+        );
+    }
+
+    #[test]
+    fn table_data_query_as_key() {
+        let query = TableDataQuery {
+            limit_hint: Some(10),
+            ..Default::default()
+        };
+        let queries = HashSet::from([query.clone(), query, TableDataQuery::default()]);
+        assert_eq!(queries.len(), 2);
+    }
+
+    #[test]
+    fn render_partition_filter() {
+        let query = TableDataQuery::default().with_partition_filter("date", "2021-04-28");
+        let body = serde_json::to_value(&query).unwrap();
+        let hints = body["jsonPredicateHints"].as_str().unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(hints).unwrap(),
+            json!({
+                "op": "equal",
+                "children": [
+                    {"op": "column", "name": "date", "valueType": "string"},
+                    {"op": "literal", "value": "2021-04-28", "valueType": "string"},
+                ],
+            })
+        );
+
+        let query = query.with_partition_filter("country", "NL");
+        let body = serde_json::to_value(&query).unwrap();
+        let hints = body["jsonPredicateHints"].as_str().unwrap();
+        let hints = serde_json::from_str::<Value>(hints).unwrap();
+        assert_eq!(hints["op"], "and");
+        assert_eq!(hints["children"][0]["children"][0]["name"], "country");
+        assert_eq!(hints["children"][1]["children"][0]["name"], "date");
+    }
+
+    #[test]
+    fn validate_partition_filters() {
+        let body = concat!(
+            r#"{"protocol":{"minReaderVersion":1}}"#,
+            "\n",
+            r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{\"type\":\"struct\",\"fields\":[{\"name\":\"date\",\"type\":\"date\",\"nullable\":true,\"metadata\":{}}]}","partitionColumns":["date"]}}"#,
+        );
+        let response = QueryTableMetadataResponse::from_ndjson(1, body, false).unwrap();
+        let metadata = TableMetadata::try_from(response).unwrap();
+
+        let query = TableDataQuery::default().with_partition_filter("date", "2021-04-28");
+        assert!(query.validate_partition_filters(&metadata).is_ok());
+
+        let query = query.with_partition_filter("id", "1");
+        assert_eq!(
+            query
+                .validate_partition_filters(&metadata)
+                .unwrap_err()
+                .to_string(),
+            "[REQUEST_ERROR] partition filter on `id`, which is not a partition column of the table"
+        );
+    }
+
+    #[test]
+    fn validate_table_data_query() {
+        let timestamp = DateTime::from_timestamp(1_627_776_000, 0).unwrap();
+        let valid = [
+            TableDataQuery::default(),
+            TableDataQuery::default().with_version(u64::from(u32::MAX) + 1),
+            TableDataQuery::default().with_timestamp(timestamp),
+            TableDataQuery::default().with_starting_version(1),
+            TableDataQuery::default()
+                .with_starting_version(1)
+                .with_ending_version(1),
+        ];
+        for query in valid {
+            assert!(query.validate().is_ok(), "{query:?}");
+        }
+
+        let invalid = [
+            (
+                TableDataQuery::default()
+                    .with_version(1)
+                    .with_timestamp(timestamp),
+                "version and timestamp cannot both be set",
+            ),
+            (
+                TableDataQuery::default()
+                    .with_version(1)
+                    .with_starting_version(1),
+                "version cannot be combined with a starting or ending version",
+            ),
+            (
+                TableDataQuery::default()
+                    .with_version(1)
+                    .with_ending_version(2),
+                "version cannot be combined with a starting or ending version",
+            ),
+            (
+                TableDataQuery::default()
+                    .with_timestamp(timestamp)
+                    .with_starting_version(1),
+                "timestamp cannot be combined with a starting or ending version",
+            ),
+            (
+                TableDataQuery::default()
+                    .with_timestamp(timestamp)
+                    .with_ending_version(2),
+                "timestamp cannot be combined with a starting or ending version",
+            ),
+            (
+                TableDataQuery::default().with_ending_version(2),
+                "ending version requires a starting version",
+            ),
+            (
+                TableDataQuery::default()
+                    .with_starting_version(3)
+                    .with_ending_version(2),
+                "starting version cannot be greater than ending version",
+            ),
+        ];
+        for (query, message) in invalid {
+            let err = query.validate().unwrap_err();
+            assert_eq!(
+                err.message(),
+                format!("invalid table data query: {message}")
+            );
+        }
+    }
+
+    #[test]
+    fn serialize_table_data_query_timestamp() {
+        let timestamp = DateTime::from_timestamp(1_627_776_000, 0).unwrap();
+        let query = TableDataQuery::default().with_timestamp(timestamp);
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({"timestamp": "2021-08-01T00:00:00Z"})
+        );
+    }
 }
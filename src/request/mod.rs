@@ -1,25 +1,166 @@
+use std::fmt;
+use std::str::FromStr;
+
 use url::Url;
 
+use crate::response::Table;
+use crate::{DeltaSharingError, Result};
+
 pub mod body;
 pub mod pagination;
 pub mod query;
 
-trait UrlExt<Q> {
-    fn with_query_params(self, params: Q) -> Self;
+pub(crate) trait UrlExt<Q> {
+    fn with_query_params(self, params: &Q) -> Self;
 }
 
-trait Query {
-    fn query_pairs(&self) -> impl Iterator<Item = (&str, &str)>;
+pub(crate) trait Query {
+    fn query_pairs(&self) -> impl Iterator<Item = (&str, String)>;
 }
 
 impl<Q: Query> UrlExt<Q> for Url {
-    fn with_query_params(mut self, params: Q) -> Self {
+    fn with_query_params(mut self, params: &Q) -> Self {
         {
             let mut query_pairs = self.query_pairs_mut();
             for (key, value) in params.query_pairs() {
-                query_pairs.append_pair(key, value);
+                query_pairs.append_pair(key, &value);
             }
         }
         self
     }
 }
+
+/// Response format of table queries, requested through the
+/// `delta-sharing-capabilities` request header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResponseFormat {
+    /// Parquet format, supported by every server.
+    Parquet,
+    /// Delta format, required to read tables with advanced Delta features
+    /// such as deletion vectors.
+    Delta,
+    /// Advertise both formats, preferring delta. The server picks a format
+    /// it supports and the response is parsed in whichever format it sent.
+    Auto,
+}
+
+impl ResponseFormat {
+    /// Retrieve the value of the `delta-sharing-capabilities` request header
+    /// for this format.
+    pub(crate) fn capabilities_header(&self) -> &'static str {
+        match self {
+            ResponseFormat::Parquet => "responseformat=parquet",
+            ResponseFormat::Delta => "responseformat=delta",
+            ResponseFormat::Auto => "responseformat=delta,parquet",
+        }
+    }
+}
+
+/// Reference to a table by its share, schema and table name.
+///
+/// A reference parses from and formats to the `share.schema.table` form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TableRef {
+    share: String,
+    schema: String,
+    name: String,
+}
+
+impl TableRef {
+    pub fn new(
+        share: impl Into<String>,
+        schema: impl Into<String>,
+        name: impl Into<String>,
+    ) -> Self {
+        Self {
+            share: share.into(),
+            schema: schema.into(),
+            name: name.into(),
+        }
+    }
+
+    /// Retrieve the name of the share the table belongs to
+    pub fn share(&self) -> &str {
+        &self.share
+    }
+
+    /// Retrieve the name of the schema the table belongs to
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// Retrieve the name of the table
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl FromStr for TableRef {
+    type Err = DeltaSharingError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split('.').collect::<Vec<_>>()[..] {
+            [share, schema, name]
+                if !share.is_empty() && !schema.is_empty() && !name.is_empty() =>
+            {
+                Ok(TableRef::new(share, schema, name))
+            }
+            _ => {
+                tracing::error!(table = s, "Cannot parse TableRef");
+                Err(DeltaSharingError::request(
+                    "Cannot parse TableRef. The string must be in the form `share.schema.table`.",
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for TableRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.share, self.schema, self.name)
+    }
+}
+
+impl From<&Table> for TableRef {
+    fn from(table: &Table) -> Self {
+        TableRef::new(table.share(), table.schema(), table.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_table_ref() {
+        let table_ref = "share.schema.table".parse::<TableRef>().unwrap();
+        assert_eq!(table_ref, TableRef::new("share", "schema", "table"));
+        assert_eq!(table_ref.to_string(), "share.schema.table");
+
+        for invalid in ["share.schema", "share..table", "a.b.c.d"] {
+            let err = invalid.parse::<TableRef>().unwrap_err();
+            assert_eq!(
+                err.message(),
+                "Cannot parse TableRef. The string must be in the form `share.schema.table`."
+            );
+        }
+    }
+
+    #[test]
+    fn table_full_name_round_trips() {
+        let table: Table = serde_json::from_str(
+            r#"{"share":"vaccine_share","schema":"acme_vaccine_data","name":"vaccine_ingredients"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            table.full_name(),
+            "vaccine_share.acme_vaccine_data.vaccine_ingredients"
+        );
+
+        let table_ref = TableRef::from_str(&table.full_name()).unwrap();
+        assert_eq!(table_ref, TableRef::from(&table));
+        assert_eq!(table_ref.share(), table.share());
+        assert_eq!(table_ref.schema(), table.schema());
+        assert_eq!(table_ref.name(), table.name());
+    }
+}
@@ -2,15 +2,26 @@
 
 use url::Url;
 
+use crate::{DeltaSharingError, Result};
+
 const QUERY_PARAM_MAX_RESULTS: &str = "maxResults";
 const QUERY_PARAM_PAGE_TOKEN: &str = "pageToken";
 
+/// Default upper bound for the number of results per page.
+pub const DEFAULT_MAX_RESULTS_LIMIT: u32 = 500;
+
 /// Pagination information for the request.
+///
+/// Paging terminates exactly when the server omits the next page token or
+/// returns an empty one. The number of items on a page is not taken into
+/// account: a full page without a next page token is the last page, and a
+/// page with a token is followed even if it holds fewer items than requested.
 #[derive(Debug)]
 pub struct Pagination {
     max_results: Option<u32>,
     page_token: Option<String>,
     is_start: bool,
+    max_results_limit: u32,
 }
 
 impl Pagination {
@@ -20,6 +31,7 @@ impl Pagination {
             max_results,
             page_token,
             is_start,
+            max_results_limit: DEFAULT_MAX_RESULTS_LIMIT,
         }
     }
 
@@ -32,12 +44,65 @@ impl Pagination {
         Self::new(max_results, Some(page_token), false)
     }
 
+    /// Set the maximum number of results per page.
+    ///
+    /// Values above the configured upper bound are clamped to that bound.
+    pub fn with_max_results(mut self, max_results: u32) -> Self {
+        self.max_results = Some(self.clamp_max_results(max_results));
+        self
+    }
+
+    /// Set the upper bound for the number of results per page, typically the
+    /// maximum supported by the server. Defaults to
+    /// [`DEFAULT_MAX_RESULTS_LIMIT`].
+    pub fn with_max_results_limit(mut self, limit: u32) -> Self {
+        self.max_results_limit = limit;
+        self.max_results = self.max_results.map(|m| self.clamp_max_results(m));
+        self
+    }
+
+    fn clamp_max_results(&self, max_results: u32) -> u32 {
+        if max_results > self.max_results_limit {
+            tracing::warn!(
+                max_results,
+                limit = self.max_results_limit,
+                "clamping max results to upper bound"
+            );
+            self.max_results_limit
+        } else {
+            max_results
+        }
+    }
+
+    pub(crate) fn page_token(&self) -> Option<&str> {
+        self.page_token.as_deref()
+    }
+
     /// Set the next page token
     pub fn set_page_token<S: Into<String>>(&mut self, token: Option<S>) {
         self.is_start = false;
         self.page_token = token.map(Into::into);
     }
 
+    /// Advance to the page of the next page token returned by the server.
+    ///
+    /// Fails if the server returns the current (non-empty) page token again,
+    /// since following it would request the same page forever.
+    pub fn advance<S: Into<String>>(&mut self, token: Option<S>) -> Result<()> {
+        let token = token.map(Into::into);
+        let is_repeated = token.as_deref().is_some_and(|t| !t.is_empty())
+            && token.as_deref() == self.page_token.as_deref();
+        if is_repeated {
+            tracing::error!(token = ?token, "server repeated page token");
+            return Err(DeltaSharingError::parse_response(
+                "server returned the same page token twice in a row",
+            ));
+        }
+
+        self.set_page_token(token);
+        Ok(())
+    }
+
     /// Check if there is another page of results
     pub fn has_next_page(&self) -> bool {
         self.is_start || (self.page_token.is_some() && self.page_token.as_deref() != Some(""))
@@ -102,6 +167,25 @@ mod test {
         assert!(pagination.has_next_page());
     }
 
+    #[test]
+    fn clamp_max_results() {
+        let pagination = Pagination::default().with_max_results(100);
+        assert_eq!(pagination.max_results, Some(100));
+
+        let pagination = Pagination::default().with_max_results(1000);
+        assert_eq!(pagination.max_results, Some(DEFAULT_MAX_RESULTS_LIMIT));
+
+        let pagination = Pagination::default()
+            .with_max_results(100)
+            .with_max_results_limit(50);
+        assert_eq!(pagination.max_results, Some(50));
+
+        let pagination = Pagination::default()
+            .with_max_results_limit(1000)
+            .with_max_results(1000);
+        assert_eq!(pagination.max_results, Some(1000));
+    }
+
     #[test]
     fn advance_pagination_from_start() {
         let mut p = Pagination::from_start(None);
@@ -126,6 +210,34 @@ mod test {
         assert!(!p.has_next_page());
     }
 
+    #[test]
+    fn advance_pagination_detects_repeated_token() {
+        let mut p = Pagination::from_start(None);
+        p.advance(Some("foo")).unwrap();
+        p.advance(Some("bar")).unwrap();
+
+        let err = p.advance(Some("bar")).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "server returned the same page token twice in a row"
+        );
+        assert!(!err.is_retriable());
+
+        p.advance::<String>(None).unwrap();
+        assert!(p.is_finished());
+    }
+
+    #[test]
+    fn full_page_without_token_is_last_page() {
+        let mut p = Pagination::from_start(Some(2));
+        p.advance::<String>(None).unwrap();
+        assert!(p.is_finished());
+
+        let mut p = Pagination::from_start(Some(2));
+        p.advance(Some("")).unwrap();
+        assert!(p.is_finished());
+    }
+
     #[test]
     fn add_pagination_to_url() {
         let pagination = Pagination::from_token(Some(7), "foo".to_owned());
@@ -1,21 +1,42 @@
 use std::str::FromStr;
+use std::time::SystemTime;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+#[cfg(feature = "serde")]
 use serde::Serialize;
 
+use super::Query;
 use crate::DeltaSharingError;
 use crate::Result;
 
+const QUERY_PARAM_STARTING_VERSION: &str = "startingVersion";
+const QUERY_PARAM_ENDING_VERSION: &str = "endingVersion";
+const QUERY_PARAM_STARTING_TIMESTAMP: &str = "startingTimestamp";
+const QUERY_PARAM_ENDING_TIMESTAMP: &str = "endingTimestamp";
+const QUERY_PARAM_HISTORICAL_METADATA: &str = "includeHistoricalMetadata";
+const QUERY_PARAM_MAX_FILES: &str = "maxFiles";
+const QUERY_PARAM_PAGE_TOKEN: &str = "pageToken";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TableVersionQuery {
     Latest,
     Timestamp(DateTime<Utc>),
+    /// Version relative to the latest version, e.g. `-1` for the version
+    /// before the latest one. Resolving a relative version requires an extra
+    /// request for the latest version; the result is clamped to the range
+    /// from zero to the latest version.
+    Relative(i64),
 }
 
 impl TableVersionQuery {
+    /// Create a query for the table version at midnight UTC of the given date.
+    pub fn from_date(date: NaiveDate) -> Self {
+        TableVersionQuery::Timestamp(date.and_time(NaiveTime::MIN).and_utc())
+    }
+
     pub fn to_timestamp(&self) -> Option<String> {
         match self {
-            TableVersionQuery::Latest => None,
+            TableVersionQuery::Latest | TableVersionQuery::Relative(_) => None,
             TableVersionQuery::Timestamp(ts) => {
                 Some(ts.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
             }
@@ -23,6 +44,19 @@ impl TableVersionQuery {
     }
 }
 
+impl TableVersionQuery {
+    /// Resolve the version against the latest version of the table. Only
+    /// relative versions change the latest version.
+    pub(crate) fn resolve(&self, latest: u64) -> u64 {
+        match self {
+            TableVersionQuery::Relative(offset) => {
+                latest.saturating_add_signed(*offset).min(latest)
+            }
+            TableVersionQuery::Latest | TableVersionQuery::Timestamp(_) => latest,
+        }
+    }
+}
+
 impl Default for TableVersionQuery {
     fn default() -> Self {
         TableVersionQuery::Latest
@@ -51,14 +85,33 @@ impl From<DateTime<Utc>> for TableVersionQuery {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+impl From<SystemTime> for TableVersionQuery {
+    fn from(ts: SystemTime) -> Self {
+        TableVersionQuery::Timestamp(ts.into())
+    }
+}
+
+/// Range of table versions, serialized as the `starting*` and `ending*`
+/// parameters of the Delta Sharing protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(untagged))]
 pub enum VersionRange {
     Number {
+        #[cfg_attr(feature = "serde", serde(rename = "startingVersion"))]
         start: u32,
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "endingVersion", skip_serializing_if = "Option::is_none")
+        )]
         end: Option<u32>,
     },
     Timestamp {
+        #[cfg_attr(feature = "serde", serde(rename = "startingTimestamp"))]
         start: DateTime<Utc>,
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "endingTimestamp", skip_serializing_if = "Option::is_none")
+        )]
         end: Option<DateTime<Utc>>,
     },
 }
@@ -79,10 +132,25 @@ impl VersionRange {
     }
 }
 
-#[derive(Debug, Serialize)]
+/// The default range covers all versions, starting at version 0.
+impl Default for VersionRange {
+    fn default() -> Self {
+        VersionRange::new_number(0, None)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+#[must_use = "queries do nothing unless passed to the client"]
 pub struct TableChangesQuery {
+    #[cfg_attr(feature = "serde", serde(flatten))]
     range: VersionRange,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     include_historical_metadata: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    max_files: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    page_token: Option<String>,
 }
 
 impl TableChangesQuery {
@@ -90,18 +158,168 @@ impl TableChangesQuery {
         Self {
             range: version_range,
             include_historical_metadata: None,
+            max_files: None,
+            page_token: None,
         }
     }
 
+    /// Create a builder to assemble a query from its version or timestamp
+    /// bounds.
+    ///
+    /// # Example
+    /// ```rust
+    /// use delta_sharing_client_rs::request::query::TableChangesQuery;
+    ///
+    /// let query = TableChangesQuery::builder()
+    ///     .with_starting_version(1)
+    ///     .with_ending_version(5)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> TableChangesQueryBuilder {
+        TableChangesQueryBuilder::new()
+    }
+
     pub fn with_historical_metadata(mut self, include: bool) -> Self {
         self.include_historical_metadata = Some(include);
         self
     }
+
+    /// Ask the server to return at most `max_files` files per page.
+    ///
+    /// The client follows the page tokens returned by the server, see
+    /// [`DeltaSharingClient::get_table_changes_stream`](crate::DeltaSharingClient::get_table_changes_stream).
+    pub fn with_max_files(mut self, max_files: u32) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    pub(crate) fn with_page_token(mut self, page_token: Option<&str>) -> Self {
+        self.page_token = page_token.map(str::to_owned);
+        self
+    }
+}
+
+/// Builder for a [`TableChangesQuery`].
+///
+/// The bounds of the range are either versions or timestamps; a starting
+/// bound is required.
+#[derive(Debug, Default, Clone)]
+#[must_use = "builders do nothing unless `build` is called"]
+pub struct TableChangesQueryBuilder {
+    starting_version: Option<u32>,
+    ending_version: Option<u32>,
+    starting_timestamp: Option<DateTime<Utc>>,
+    ending_timestamp: Option<DateTime<Utc>>,
+    include_historical_metadata: Option<bool>,
+}
+
+impl TableChangesQueryBuilder {
+    /// Create a new, empty query builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the first version of the range, inclusive.
+    pub fn with_starting_version(mut self, version: u32) -> Self {
+        self.starting_version = Some(version);
+        self
+    }
+
+    /// Set the last version of the range, inclusive.
+    pub fn with_ending_version(mut self, version: u32) -> Self {
+        self.ending_version = Some(version);
+        self
+    }
+
+    /// Set the timestamp of the first version of the range.
+    pub fn with_starting_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.starting_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Set the timestamp of the last version of the range.
+    pub fn with_ending_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.ending_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Include the historical metadata of the table in the response.
+    pub fn with_historical_metadata(mut self, include: bool) -> Self {
+        self.include_historical_metadata = Some(include);
+        self
+    }
+
+    /// Validate the configured bounds and build the query.
+    pub fn build(self) -> Result<TableChangesQuery> {
+        let has_versions = self.starting_version.is_some() || self.ending_version.is_some();
+        let has_timestamps = self.starting_timestamp.is_some() || self.ending_timestamp.is_some();
+        if has_versions && has_timestamps {
+            tracing::error!(builder = ?self, "mixed version and timestamp bounds");
+            return Err(DeltaSharingError::request(
+                "Cannot build TableChangesQuery. Version and timestamp bounds cannot be mixed.",
+            ));
+        }
+
+        let range = match (self.starting_version, self.starting_timestamp) {
+            (Some(start), _) => VersionRange::new_number(start, self.ending_version),
+            (_, Some(start)) => VersionRange::new_timestamp(start, self.ending_timestamp),
+            (None, None) => {
+                tracing::error!(builder = ?self, "missing starting bound");
+                return Err(DeltaSharingError::request(
+                    "Cannot build TableChangesQuery. A starting version or timestamp is required.",
+                ));
+            }
+        };
+        let query = TableChangesQuery::new(range);
+        Ok(match self.include_historical_metadata {
+            Some(include) => query.with_historical_metadata(include),
+            None => query,
+        })
+    }
+}
+
+impl Query for TableChangesQuery {
+    fn query_pairs(&self) -> impl Iterator<Item = (&str, String)> {
+        let to_timestamp =
+            |ts: &DateTime<Utc>| ts.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        let (start, end) = match &self.range {
+            VersionRange::Number { start, end } => (
+                (QUERY_PARAM_STARTING_VERSION, start.to_string()),
+                end.map(|e| (QUERY_PARAM_ENDING_VERSION, e.to_string())),
+            ),
+            VersionRange::Timestamp { start, end } => (
+                (QUERY_PARAM_STARTING_TIMESTAMP, to_timestamp(start)),
+                end.as_ref()
+                    .map(|e| (QUERY_PARAM_ENDING_TIMESTAMP, to_timestamp(e))),
+            ),
+        };
+        let historical_metadata = self
+            .include_historical_metadata
+            .map(|h| (QUERY_PARAM_HISTORICAL_METADATA, h.to_string()));
+        let max_files = self
+            .max_files
+            .map(|m| (QUERY_PARAM_MAX_FILES, m.to_string()));
+        let page_token = self.page_token.clone().map(|t| (QUERY_PARAM_PAGE_TOKEN, t));
+
+        std::iter::once(start)
+            .chain(end)
+            .chain(historical_metadata)
+            .chain(max_files)
+            .chain(page_token)
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashSet;
+
+    #[cfg(feature = "serde")]
+    use serde_json::json;
+    use url::Url;
+
     use super::*;
+    use crate::request::UrlExt;
 
     #[test]
     fn default_table_version_query() {
@@ -116,6 +334,34 @@ mod test {
         assert_eq!(table_version, TableVersionQuery::Timestamp(ts));
     }
 
+    #[test]
+    fn table_version_query_from_system_time() {
+        let ts = SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1627776000_500);
+        let table_version = TableVersionQuery::from(ts);
+        assert_eq!(
+            table_version.to_timestamp(),
+            Some("2021-08-01T00:00:00Z".to_string())
+        );
+    }
+
+    #[test]
+    fn table_version_query_from_date() {
+        let date = NaiveDate::from_ymd_opt(2021, 8, 1).unwrap();
+        let table_version = TableVersionQuery::from_date(date);
+        assert_eq!(
+            table_version.to_timestamp(),
+            Some("2021-08-01T00:00:00Z".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_relative_version() {
+        assert_eq!(TableVersionQuery::Relative(-1).resolve(5), 4);
+        assert_eq!(TableVersionQuery::Relative(0).resolve(5), 5);
+        assert_eq!(TableVersionQuery::Relative(-10).resolve(5), 0);
+        assert_eq!(TableVersionQuery::Relative(3).resolve(5), 5);
+    }
+
     #[test]
     fn parse_table_version_query() {
         let parsed_table_version = "latest".parse::<TableVersionQuery>().unwrap();
@@ -147,4 +393,144 @@ mod test {
         let table_version = TableVersionQuery::Latest;
         assert_eq!(table_version.to_timestamp(), None);
     }
+
+    #[test]
+    fn table_changes_query_pairs_version_range() {
+        let query = TableChangesQuery::new(VersionRange::new_number(1, Some(5)))
+            .with_historical_metadata(true);
+        let pairs = query.query_pairs().collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            vec![
+                ("startingVersion", String::from("1")),
+                ("endingVersion", String::from("5")),
+                ("includeHistoricalMetadata", String::from("true")),
+            ]
+        );
+    }
+
+    #[test]
+    fn table_changes_query_as_key() {
+        let query = TableChangesQuery::new(VersionRange::new_number(1, Some(5)));
+        let queries = HashSet::from([
+            query.clone(),
+            query,
+            TableChangesQuery::new(VersionRange::new_number(1, None)),
+        ]);
+        assert_eq!(queries.len(), 2);
+    }
+
+    #[test]
+    fn table_changes_query_emits_starting_version_zero() {
+        let query = TableChangesQuery::new(VersionRange::new_number(0, Some(5)));
+        let url = Url::parse("https://sharing.delta.io/changes")
+            .unwrap()
+            .with_query_params(&query);
+        assert_eq!(url.query(), Some("startingVersion=0&endingVersion=5"));
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({"startingVersion": 0, "endingVersion": 5})
+        );
+    }
+
+    #[test]
+    fn table_changes_query_pairs_timestamp_range() {
+        let start = DateTime::<Utc>::from_timestamp(1627776000, 0).unwrap();
+        let end = DateTime::<Utc>::from_timestamp(1627862400, 0).unwrap();
+        let query = TableChangesQuery::new(VersionRange::new_timestamp(start, Some(end)));
+        let pairs = query.query_pairs().collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            vec![
+                ("startingTimestamp", String::from("2021-08-01T00:00:00Z")),
+                ("endingTimestamp", String::from("2021-08-02T00:00:00Z")),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize_table_changes_query_version_range() {
+        let query = TableChangesQuery::new(VersionRange::new_number(1, Some(5)))
+            .with_historical_metadata(true);
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({"startingVersion": 1, "endingVersion": 5, "includeHistoricalMetadata": true})
+        );
+
+        let query = TableChangesQuery::new(VersionRange::new_number(1, None));
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({"startingVersion": 1})
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize_table_changes_query_timestamp_range() {
+        let start = DateTime::<Utc>::from_timestamp(1627776000, 0).unwrap();
+        let end = DateTime::<Utc>::from_timestamp(1627862400, 0).unwrap();
+        let query = TableChangesQuery::new(VersionRange::new_timestamp(start, Some(end)));
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            json!({"startingTimestamp": "2021-08-01T00:00:00Z", "endingTimestamp": "2021-08-02T00:00:00Z"})
+        );
+    }
+
+    #[test]
+    fn build_table_changes_query() {
+        let query = TableChangesQuery::builder()
+            .with_starting_version(1)
+            .with_ending_version(5)
+            .with_historical_metadata(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            query,
+            TableChangesQuery::new(VersionRange::new_number(1, Some(5)))
+                .with_historical_metadata(true)
+        );
+
+        let start = DateTime::parse_from_rfc3339("2021-08-01T00:00:00Z")
+            .unwrap()
+            .into();
+        let end = DateTime::parse_from_rfc3339("2021-08-02T00:00:00Z")
+            .unwrap()
+            .into();
+        let query = TableChangesQuery::builder()
+            .with_starting_timestamp(start)
+            .with_ending_timestamp(end)
+            .build()
+            .unwrap();
+        assert_eq!(
+            query,
+            TableChangesQuery::new(VersionRange::new_timestamp(start, Some(end)))
+        );
+
+        let err = TableChangesQuery::builder()
+            .with_starting_version(1)
+            .with_ending_timestamp(end)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Cannot build TableChangesQuery. Version and timestamp bounds cannot be mixed."
+        );
+
+        let err = TableChangesQuery::builder()
+            .with_ending_version(5)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "Cannot build TableChangesQuery. A starting version or timestamp is required."
+        );
+
+        assert_eq!(
+            TableChangesQuery::default(),
+            TableChangesQuery::new(VersionRange::new_number(0, None))
+        );
+    }
 }
@@ -1,6 +1,14 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use delta_kernel::actions::{Add, Metadata, Protocol};
 use serde::{Deserialize, Serialize};
 
+use super::FileAction;
+
+pub(crate) const READER_FEATURE_DELETION_VECTORS: &str = "deletionVectors";
+pub(crate) const READER_FEATURE_COLUMN_MAPPING: &str = "columnMapping";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum DeltaAction {
@@ -10,18 +18,77 @@ pub enum DeltaAction {
     File(DeltaFileAction),
 }
 
+impl DeltaAction {
+    pub fn is_protocol(&self) -> bool {
+        matches!(self, DeltaAction::Protocol(_))
+    }
+
+    pub fn is_metadata(&self) -> bool {
+        matches!(self, DeltaAction::Metadata(_))
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self, DeltaAction::File(_))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeltaProtocolAction {
     delta_protocol: Protocol,
 }
 
+impl DeltaProtocolAction {
+    /// Retrieve the Delta protocol of the table
+    pub fn delta_protocol(&self) -> &Protocol {
+        &self.delta_protocol
+    }
+
+    /// Retrieve the minimum reader version required to read the table
+    pub fn min_reader_version(&self) -> i32 {
+        self.delta_protocol.min_reader_version
+    }
+
+    /// Retrieve the minimum writer version required to write the table
+    pub fn min_writer_version(&self) -> i32 {
+        self.delta_protocol.min_writer_version
+    }
+
+    /// Retrieve the reader features required to read the table
+    pub fn reader_features(&self) -> &[String] {
+        self.delta_protocol
+            .reader_features
+            .as_deref()
+            .unwrap_or_default()
+    }
+
+    /// Check whether readers must support deletion vectors
+    pub fn requires_deletion_vectors(&self) -> bool {
+        self.reader_features()
+            .iter()
+            .any(|feature| feature == READER_FEATURE_DELETION_VECTORS)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeltaMetadataAction {
     delta_metadata: Metadata,
 }
 
+impl DeltaMetadataAction {
+    /// Retrieve the Delta metadata of the table
+    pub fn delta_metadata(&self) -> &Metadata {
+        &self.delta_metadata
+    }
+}
+
+impl From<DeltaMetadataAction> for Metadata {
+    fn from(metadata: DeltaMetadataAction) -> Self {
+        metadata.delta_metadata
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum DeltaSingleAction {
@@ -38,3 +105,116 @@ pub struct DeltaFileAction {
     expiration_timestamp: Option<u64>,
     delta_single_action: DeltaSingleAction,
 }
+
+impl DeltaFileAction {
+    /// Retrieve the presigned URL of the file
+    pub fn url(&self) -> &str {
+        match &self.delta_single_action {
+            DeltaSingleAction::Add(add) => &add.path,
+        }
+    }
+
+    /// Retrieve the presigned URL of the file, stored as the path of the
+    /// inner add action
+    pub fn path(&self) -> &str {
+        self.url()
+    }
+
+    pub(crate) fn set_url(&mut self, url: String) {
+        match &mut self.delta_single_action {
+            DeltaSingleAction::Add(add) => add.path = url,
+        }
+    }
+
+    /// Retrieve the id of the file
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Retrieve the size of the file in bytes
+    pub fn size(&self) -> u64 {
+        match &self.delta_single_action {
+            DeltaSingleAction::Add(add) => u64::try_from(add.size).unwrap_or_default(),
+        }
+    }
+
+    /// Retrieve the partition values of the file
+    pub fn partition_values(&self) -> &HashMap<String, Option<String>> {
+        match &self.delta_single_action {
+            DeltaSingleAction::Add(add) => &add.partition_values,
+        }
+    }
+
+    /// Retrieve the statistics of the file as a JSON string, if available
+    pub fn stats(&self) -> Option<&str> {
+        match &self.delta_single_action {
+            DeltaSingleAction::Add(add) => add.stats.as_deref(),
+        }
+    }
+
+    /// Retrieve the table version of the file, if returned by the server
+    pub fn version(&self) -> Option<u64> {
+        self.version
+    }
+
+    /// Retrieve the commit timestamp of the file, if returned by the server
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+            .and_then(|ts| i64::try_from(ts).ok())
+            .and_then(DateTime::from_timestamp_millis)
+    }
+}
+
+impl FileAction for DeltaFileAction {
+    fn url(&self) -> &str {
+        DeltaFileAction::url(self)
+    }
+
+    fn id(&self) -> &str {
+        DeltaFileAction::id(self)
+    }
+
+    fn size(&self) -> u64 {
+        DeltaFileAction::size(self)
+    }
+
+    fn partition_values(&self) -> &HashMap<String, Option<String>> {
+        DeltaFileAction::partition_values(self)
+    }
+
+    fn stats(&self) -> Option<&str> {
+        DeltaFileAction::stats(self)
+    }
+
+    fn version(&self) -> Option<u64> {
+        DeltaFileAction::version(self)
+    }
+
+    fn expiration_time(&self) -> Option<DateTime<Utc>> {
+        self.expiration_timestamp
+            .and_then(|ts| i64::try_from(ts).ok())
+            .and_then(DateTime::from_timestamp_millis)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn file_action_accessors() {
+        let line = r#"{"file":{"id":"c1","deltaSingleAction":{"add":{"path":"https://example.com/part-0.parquet","partitionValues":{"date":"2021-04-28"},"size":573,"modificationTime":1619591469000,"dataChange":true,"stats":"{\"numRecords\":1}"}}}}"#;
+
+        let action = serde_json::from_str::<DeltaAction>(line).unwrap();
+        let DeltaAction::File(file) = action else {
+            panic!("expected file action");
+        };
+        assert_eq!(file.path(), "https://example.com/part-0.parquet");
+        assert_eq!(file.size(), 573);
+        assert_eq!(
+            file.partition_values().get("date"),
+            Some(&Some(String::from("2021-04-28")))
+        );
+        assert_eq!(file.stats(), Some(r#"{"numRecords":1}"#));
+    }
+}
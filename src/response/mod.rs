@@ -1,43 +1,149 @@
 //! Delta Sharing server response types.
 
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use chrono::{DateTime, Utc};
 
 use delta_kernel::actions::{Add, Metadata, Protocol};
+use delta_kernel::schema::{SchemaRef, StructType};
+use futures::stream::{BoxStream, Stream, StreamExt, TryStreamExt};
+use serde::de::{DeserializeOwned, IgnoredAny};
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 use self::{delta::DeltaAction, parquet::ParquetAction};
+use crate::{DeltaSharingError, Result};
 
-mod delta;
-mod parquet;
+pub mod delta;
+pub mod parquet;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+const TABLE_PROPERTY_ENABLE_CDF: &str = "delta.enableChangeDataFeed";
+
+// The derived orderings compare fields in declaration order, so the fields are
+// declared from the outermost to the innermost part of the qualified name.
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Share {
-    id: Option<String>,
     name: String,
+    id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Share {
+    /// Retrieve the name of the share
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the id of the share
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Schema {
-    name: String,
     share: String,
+    name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Schema {
+    /// Retrieve the name of the share the schema belongs to
+    pub fn share(&self) -> &str {
+        &self.share
+    }
+
+    /// Retrieve the name of the schema
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Table {
-    name: String,
-    schema: String,
     share: String,
+    schema: String,
+    name: String,
     share_id: Option<String>,
     id: Option<String>,
 }
 
+impl Table {
+    /// Retrieve the name of the share the table belongs to
+    pub fn share(&self) -> &str {
+        &self.share
+    }
+
+    /// Retrieve the name of the schema the table belongs to
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// Retrieve the name of the table
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the id of the share the table belongs to
+    pub fn share_id(&self) -> Option<&str> {
+        self.share_id.as_deref()
+    }
+
+    /// Retrieve the id of the table
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Retrieve the fully qualified name of the table as `share.schema.name`
+    pub fn full_name(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.share, self.schema, self.name)
+    }
+}
+
+/// Share with its schemas, as part of a catalog tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareNode {
+    pub share: Share,
+    pub schemas: Vec<SchemaNode>,
+}
+
+/// Schema with its tables, as part of a catalog tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaNode {
+    pub schema: Schema,
+    pub tables: Vec<Table>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ListResponse<T> {
     items: Vec<T>,
     next_page_token: Option<String>,
+    #[serde(default, alias = "total", skip_serializing_if = "Option::is_none")]
+    total_count: Option<u64>,
 }
 
 impl<T> ListResponse<T> {
+    /// Create a list response, e.g. to test code consuming list responses
+    /// without a server.
+    pub fn new(items: Vec<T>, next_page_token: Option<String>) -> Self {
+        Self {
+            items,
+            next_page_token,
+            total_count: None,
+        }
+    }
+
     pub fn items(&self) -> &[T] {
         &self.items
     }
@@ -45,6 +151,25 @@ impl<T> ListResponse<T> {
     pub fn next_page_token(&self) -> Option<&str> {
         self.next_page_token.as_deref()
     }
+
+    /// Retrieve the total number of items across all pages, if the server
+    /// reports it as `totalCount` or `total`
+    pub fn total_count(&self) -> Option<u64> {
+        self.total_count
+    }
+}
+
+/// Collect items into a list response without a next page token.
+impl<T> FromIterator<T> for ListResponse<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect(), None)
+    }
+}
+
+impl<T> Extend<T> for ListResponse<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.items.extend(iter);
+    }
 }
 
 impl<T> IntoIterator for ListResponse<T> {
@@ -70,7 +195,7 @@ pub type ListSchemasResponse = ListResponse<Schema>;
 pub type ListTablesResponse = ListResponse<Table>;
 
 /// Delta Sharing server response for successful `get_share` requests.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GetShareResponse {
     pub share: Share,
 }
@@ -82,32 +207,412 @@ impl GetShareResponse {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct QueryTableVersionResponse {
     version: u64,
 }
 
-#[derive(Debug, Deserialize)]
+impl QueryTableVersionResponse {
+    /// Create a response for the given table version.
+    pub fn new(version: u64) -> Self {
+        Self { version }
+    }
+
+    /// Retrieve the version of the table
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct QueryTableMetadataResponse {
     version: u64,
     protocol: TableAction,
     metadata: TableAction,
 }
 
-#[derive(Debug, Deserialize)]
+impl QueryTableMetadataResponse {
+    /// Parse the newline delimited JSON body of a metadata query.
+    ///
+    /// The body consists of a protocol action followed by a metadata action.
+    /// Parsing fails if the protocol requires a newer reader than this client,
+    /// or in strict mode if an action contains unknown fields.
+    pub(crate) fn from_ndjson(version: u64, body: &str, strict: bool) -> Result<Self> {
+        let mut actions = parse_ndjson_actions(body, strict)?.into_iter();
+        let protocol = next_protocol(&mut actions)?;
+        let metadata = next_metadata(&mut actions)?;
+        Ok(Self::new(version, protocol, metadata))
+    }
+
+    /// Create a response from its protocol and metadata actions, e.g. to build
+    /// expected values in tests.
+    pub fn new(version: u64, protocol: TableAction, metadata: TableAction) -> Self {
+        Self {
+            version,
+            protocol,
+            metadata,
+        }
+    }
+
+    /// Retrieve the version of the table
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Retrieve the protocol action of the table
+    pub fn protocol(&self) -> &TableAction {
+        &self.protocol
+    }
+
+    /// Retrieve the metadata action of the table
+    pub fn metadata(&self) -> &TableAction {
+        &self.metadata
+    }
+
+    /// Convert the metadata of the table into a `delta_kernel` [`Metadata`].
+    ///
+    /// Delta format responses carry the kernel metadata as is. For parquet
+    /// format responses the metadata is constructed from the metadata action,
+    /// without a creation time.
+    pub fn into_kernel_metadata(self) -> Result<Metadata> {
+        match self.metadata {
+            TableAction::Parquet(ParquetAction::Metadata(m)) => Ok(m.into()),
+            TableAction::Delta(DeltaAction::Metadata(m)) => Ok(m.into()),
+            _ => Err(DeltaSharingError::internal(
+                "expected metadata action in response",
+            )),
+        }
+    }
+}
+
+/// Metadata of a table at a specific version, with its schema parsed.
+#[derive(Debug)]
+pub struct TableMetadata {
+    version: u64,
+    metadata: Metadata,
+    schema: SchemaRef,
+}
+
+impl TableMetadata {
+    /// Retrieve the version of the table
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Retrieve the schema of the table, including partition columns
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Retrieve the names of the columns the table is partitioned by
+    pub fn partition_columns(&self) -> &[String] {
+        &self.metadata.partition_columns
+    }
+
+    /// Retrieve the table properties of the table
+    pub fn configuration(&self) -> &HashMap<String, Option<String>> {
+        &self.metadata.configuration
+    }
+
+    /// Check whether the change data feed is enabled on the table
+    pub fn change_data_feed_enabled(&self) -> bool {
+        let property = self.metadata.configuration.get(TABLE_PROPERTY_ENABLE_CDF);
+        is_enabled(property.and_then(|v| v.as_deref()))
+    }
+}
+
+impl TryFrom<QueryTableMetadataResponse> for TableMetadata {
+    type Error = DeltaSharingError;
+
+    fn try_from(response: QueryTableMetadataResponse) -> Result<Self> {
+        let version = response.version();
+        let metadata = response.into_kernel_metadata()?;
+        let schema = serde_json::from_str::<StructType>(&metadata.schema_string).map_err(|e| {
+            tracing::error!(err = ?e, "failed to parse table schema");
+            DeltaSharingError::parse_response("failed to parse table schema").with_source(e)
+        })?;
+
+        Ok(Self {
+            version,
+            metadata,
+            schema: Arc::new(schema),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct QueryTableDataResponse {
     version: u64,
     protocol: TableAction,
     metadata: TableAction,
     files: Vec<TableAction>,
+    #[serde(default)]
+    next_page_token: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl QueryTableDataResponse {
+    /// Parse the newline delimited JSON body of a data query.
+    ///
+    /// The body consists of a protocol action, a metadata action and zero or
+    /// more file actions. Parsing fails if the protocol requires a newer
+    /// reader than this client, or in strict mode if an action contains
+    /// unknown fields.
+    pub(crate) fn from_ndjson(version: u64, body: &str, strict: bool) -> Result<Self> {
+        let (body, end_stream) = split_end_stream_action(body);
+        let mut actions = parse_ndjson_actions(body, strict)?.into_iter();
+        let protocol = next_protocol(&mut actions)?;
+        let metadata = next_metadata(&mut actions)?;
+        let files = actions.collect();
+        let mut response = Self::new(version, protocol, metadata, files);
+        response.next_page_token = end_stream.and_then(|e| e.next_page_token);
+        Ok(response)
+    }
+
+    /// Create a response from its protocol, metadata and file actions, e.g. to
+    /// build expected values in tests.
+    pub fn new(
+        version: u64,
+        protocol: TableAction,
+        metadata: TableAction,
+        files: Vec<TableAction>,
+    ) -> Self {
+        Self {
+            version,
+            protocol,
+            metadata,
+            files,
+            next_page_token: None,
+        }
+    }
+
+    /// Retrieve the version of the table
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Retrieve the protocol action of the table
+    pub fn protocol(&self) -> &TableAction {
+        &self.protocol
+    }
+
+    /// Retrieve the metadata action of the table
+    pub fn metadata(&self) -> &TableAction {
+        &self.metadata
+    }
+
+    /// Retrieve the file actions of the table
+    pub fn files(&self) -> &[TableAction] {
+        &self.files
+    }
+
+    /// Retrieve the number of files returned by the query
+    pub fn file_count(&self) -> usize {
+        self.files.iter().filter(|action| action.is_file()).count()
+    }
+
+    /// Retrieve the total size in bytes of the files returned by the query
+    pub fn total_size(&self) -> u64 {
+        self.files
+            .iter()
+            .filter_map(TableAction::as_file)
+            .map(FileAction::size)
+            .sum()
+    }
+
+    /// Retrieve the token of the next page of a paginated data query, if any
+    pub fn next_page_token(&self) -> Option<&str> {
+        self.next_page_token.as_deref()
+    }
+
+    /// Append the files of the next page of a paginated data query.
+    pub(crate) fn extend_page(&mut self, page: QueryTableDataResponse) {
+        self.files.extend(page.files);
+        self.next_page_token = page.next_page_token;
+    }
+
+    /// Sort the files by table version, then by id. Files without a version
+    /// come first.
+    pub(crate) fn sort_files(&mut self) {
+        self.files
+            .sort_by(|a, b| file_sort_key(a).cmp(&file_sort_key(b)));
+    }
+
+    pub(crate) fn rewrite_file_urls(&mut self, rewrite: &(dyn Fn(Url) -> Url + Send + Sync)) {
+        for file in &mut self.files {
+            file.rewrite_url(rewrite);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct QueryTableChangesResponse {
     version: u64,
     protocol: TableAction,
     metadata: TableAction,
     files: Vec<TableAction>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+impl QueryTableChangesResponse {
+    /// Parse the newline delimited JSON body of a changes query.
+    ///
+    /// The body consists of a protocol action, a metadata action and zero or
+    /// more file actions. Parsing fails if the protocol requires a newer
+    /// reader than this client, or in strict mode if an action contains
+    /// unknown fields.
+    pub(crate) fn from_ndjson(version: u64, body: &str, strict: bool) -> Result<Self> {
+        let (body, end_stream) = split_end_stream_action(body);
+        let mut actions = parse_ndjson_actions(body, strict)?.into_iter();
+        let protocol = next_protocol(&mut actions)?;
+        let metadata = next_metadata(&mut actions)?;
+        let files = actions.collect();
+        let mut response = Self::new(version, protocol, metadata, files);
+        response.next_page_token = end_stream.and_then(|e| e.next_page_token);
+        Ok(response)
+    }
+
+    /// Create a response from its protocol, metadata and file actions, e.g. to
+    /// build expected values in tests.
+    pub fn new(
+        version: u64,
+        protocol: TableAction,
+        metadata: TableAction,
+        files: Vec<TableAction>,
+    ) -> Self {
+        Self {
+            version,
+            protocol,
+            metadata,
+            files,
+            next_page_token: None,
+        }
+    }
+
+    /// Retrieve the version of the table
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Retrieve the protocol action of the table
+    pub fn protocol(&self) -> &TableAction {
+        &self.protocol
+    }
+
+    /// Retrieve the metadata action of the table
+    pub fn metadata(&self) -> &TableAction {
+        &self.metadata
+    }
+
+    /// Retrieve the file actions of the changes
+    pub fn files(&self) -> &[TableAction] {
+        &self.files
+    }
+
+    /// Retrieve the token of the next page of a paginated changes query, if
+    /// any
+    pub fn next_page_token(&self) -> Option<&str> {
+        self.next_page_token.as_deref()
+    }
+
+    /// Append the files of the next page of a paginated changes query.
+    pub(crate) fn extend_page(&mut self, page: QueryTableChangesResponse) {
+        self.files.extend(page.files);
+        self.next_page_token = page.next_page_token;
+    }
+
+    /// Split the response into its protocol, metadata and file actions.
+    pub(crate) fn into_actions(self) -> (TableAction, TableAction, Vec<TableAction>) {
+        (self.protocol, self.metadata, self.files)
+    }
+
+    pub(crate) fn rewrite_file_urls(&mut self, rewrite: &(dyn Fn(Url) -> Url + Send + Sync)) {
+        for file in &mut self.files {
+            file.rewrite_url(rewrite);
+        }
+    }
+
+    /// Group the file actions of the changes by table version.
+    ///
+    /// The versions are in ascending order and the actions of a version keep
+    /// the order of the response. Actions without a version are left out.
+    pub fn by_version(&self) -> BTreeMap<u64, Vec<&TableAction>> {
+        let mut versions = BTreeMap::<u64, Vec<&TableAction>>::new();
+        for action in &self.files {
+            if let Some(version) = action.as_file().and_then(|f| f.version()) {
+                versions.entry(version).or_default().push(action);
+            }
+        }
+        versions
+    }
+}
+
+/// Streaming response of a data query.
+///
+/// The protocol and metadata actions are read when the stream is created.
+/// The file actions are parsed one line at a time while the body arrives, so
+/// only a single line is buffered at any time instead of the whole body.
+/// Reading stops while the stream is not polled, applying backpressure to the
+/// server connection.
+pub struct TableDataStream<'a> {
+    version: u64,
+    protocol: TableAction,
+    metadata: TableAction,
+    files: BoxStream<'a, Result<TableAction>>,
+}
+
+impl<'a> TableDataStream<'a> {
+    /// Read the protocol and metadata actions from a stream of actions. The
+    /// remaining actions are the file actions.
+    pub(crate) async fn from_actions(
+        version: u64,
+        mut actions: BoxStream<'a, Result<TableAction>>,
+    ) -> Result<Self> {
+        let protocol = actions.try_next().await?;
+        let protocol = next_protocol(&mut protocol.into_iter())?;
+        let metadata = actions.try_next().await?;
+        let metadata = next_metadata(&mut metadata.into_iter())?;
+
+        Ok(Self {
+            version,
+            protocol,
+            metadata,
+            files: actions,
+        })
+    }
+
+    /// Retrieve the version of the table
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Retrieve the protocol action of the table
+    pub fn protocol(&self) -> &TableAction {
+        &self.protocol
+    }
+
+    /// Retrieve the metadata action of the table
+    pub fn metadata(&self) -> &TableAction {
+        &self.metadata
+    }
+}
+
+impl Stream for TableDataStream<'_> {
+    type Item = Result<TableAction>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.files.poll_next_unpin(cx)
+    }
+}
+
+impl fmt::Debug for TableDataStream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableDataStream")
+            .field("version", &self.version)
+            .field("protocol", &self.protocol)
+            .field("metadata", &self.metadata)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,6 +623,66 @@ pub enum TableAction {
 }
 
 impl TableAction {
+    pub fn is_protocol(&self) -> bool {
+        match self {
+            TableAction::Parquet(p) => p.is_protocol(),
+            TableAction::Delta(d) => d.is_protocol(),
+        }
+    }
+
+    pub fn is_metadata(&self) -> bool {
+        match self {
+            TableAction::Parquet(p) => p.is_metadata(),
+            TableAction::Delta(d) => d.is_metadata(),
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        match self {
+            TableAction::Parquet(p) => p.is_file(),
+            TableAction::Delta(d) => d.is_file(),
+        }
+    }
+
+    /// Check whether the change data feed is enabled on the table.
+    ///
+    /// Returns `false` for actions other than metadata actions.
+    pub fn change_data_feed_enabled(&self) -> bool {
+        is_enabled(self.configuration_value(TABLE_PROPERTY_ENABLE_CDF))
+    }
+
+    pub(crate) fn configuration_value(&self, key: &str) -> Option<&str> {
+        let configuration = match self {
+            TableAction::Parquet(ParquetAction::Metadata(m)) => m.configuration(),
+            TableAction::Delta(DeltaAction::Metadata(m)) => &m.delta_metadata().configuration,
+            _ => return None,
+        };
+        configuration.get(key).and_then(|v| v.as_deref())
+    }
+
+    /// Rewrite the URL of a file action. Other actions and URLs that cannot
+    /// be parsed are left untouched.
+    pub(crate) fn rewrite_url(&mut self, rewrite: &(dyn Fn(Url) -> Url + Send + Sync)) {
+        let Some(file) = self.as_file() else {
+            return;
+        };
+        let Ok(url) = Url::parse(file.url()) else {
+            tracing::warn!("cannot rewrite unparsable file URL");
+            return;
+        };
+
+        let rewritten = rewrite(url).to_string();
+        match self {
+            TableAction::Parquet(p) => {
+                if let Some(f) = p.as_file_mut() {
+                    f.set_url(rewritten)
+                }
+            }
+            TableAction::Delta(DeltaAction::File(f)) => f.set_url(rewritten),
+            _ => {}
+        }
+    }
+
     pub fn is_parquet(&self) -> bool {
         matches!(self, TableAction::Parquet(_))
     }
@@ -140,6 +705,32 @@ impl TableAction {
         }
     }
 
+    /// Retrieve the action as a file action, regardless of its format.
+    pub fn as_file(&self) -> Option<&dyn FileAction> {
+        match self {
+            TableAction::Parquet(p) => p.as_file().map(|f| f as &dyn FileAction),
+            TableAction::Delta(DeltaAction::File(f)) => Some(f),
+            _ => None,
+        }
+    }
+
+    /// Dispatch the action to the matching method of the visitor, regardless
+    /// of its format.
+    pub fn visit<V: ActionVisitor + ?Sized>(&self, visitor: &mut V) {
+        match self {
+            TableAction::Parquet(ParquetAction::Protocol(_))
+            | TableAction::Delta(DeltaAction::Protocol(_)) => visitor.on_protocol(self),
+            TableAction::Parquet(ParquetAction::Metadata(_))
+            | TableAction::Delta(DeltaAction::Metadata(_)) => visitor.on_metadata(self),
+            TableAction::Parquet(ParquetAction::File(f) | ParquetAction::Add(f)) => {
+                visitor.on_file(f)
+            }
+            TableAction::Parquet(ParquetAction::Remove(f)) => visitor.on_remove(f),
+            TableAction::Parquet(ParquetAction::Cdf(f)) => visitor.on_cdc(f),
+            TableAction::Delta(DeltaAction::File(f)) => visitor.on_file(f),
+        }
+    }
+
     pub fn to_parquet(self) -> Option<ParquetAction> {
         match self {
             TableAction::Parquet(p) => Some(p),
@@ -155,6 +746,437 @@ impl TableAction {
     }
 }
 
+/// File in a table, independent of the response format.
+pub trait FileAction: fmt::Debug + Send + Sync {
+    /// Retrieve the presigned URL of the file
+    fn url(&self) -> &str;
+
+    /// Retrieve the id of the file
+    fn id(&self) -> &str;
+
+    /// Retrieve the size of the file in bytes
+    fn size(&self) -> u64;
+
+    /// Retrieve the partition values of the file
+    fn partition_values(&self) -> &HashMap<String, Option<String>>;
+
+    /// Retrieve the statistics of the file as a JSON string, if available
+    fn stats(&self) -> Option<&str>;
+
+    /// Retrieve the table version of the file, if returned by the server
+    fn version(&self) -> Option<u64>;
+
+    /// Retrieve the time at which the presigned URL expires, if returned by
+    /// the server
+    fn expiration_time(&self) -> Option<DateTime<Utc>>;
+}
+
+/// Callbacks for the kinds of table actions, see [`TableAction::visit`].
+///
+/// All callbacks default to doing nothing.
+pub trait ActionVisitor {
+    /// Called for protocol actions.
+    fn on_protocol(&mut self, action: &TableAction) {
+        let _ = action;
+    }
+
+    /// Called for metadata actions.
+    fn on_metadata(&mut self, action: &TableAction) {
+        let _ = action;
+    }
+
+    /// Called for files to read: `file` actions of data queries and `add`
+    /// actions of changes queries.
+    fn on_file(&mut self, file: &dyn FileAction) {
+        let _ = file;
+    }
+
+    /// Called for `remove` actions of changes queries.
+    fn on_remove(&mut self, file: &dyn FileAction) {
+        let _ = file;
+    }
+
+    /// Called for `cdf` actions of changes queries.
+    fn on_cdc(&mut self, file: &dyn FileAction) {
+        let _ = file;
+    }
+}
+
+impl From<ParquetAction> for TableAction {
+    fn from(action: ParquetAction) -> Self {
+        TableAction::Parquet(action)
+    }
+}
+
+impl From<DeltaAction> for TableAction {
+    fn from(action: DeltaAction) -> Self {
+        TableAction::Delta(action)
+    }
+}
+
+impl TryFrom<TableAction> for ParquetAction {
+    type Error = DeltaSharingError;
+
+    fn try_from(action: TableAction) -> Result<Self> {
+        match action {
+            TableAction::Parquet(p) => Ok(p),
+            TableAction::Delta(_) => Err(DeltaSharingError::internal(
+                "expected a parquet format action, found a delta format action",
+            )),
+        }
+    }
+}
+
+impl TryFrom<TableAction> for DeltaAction {
+    type Error = DeltaSharingError;
+
+    fn try_from(action: TableAction) -> Result<Self> {
+        match action {
+            TableAction::Delta(d) => Ok(d),
+            TableAction::Parquet(_) => Err(DeltaSharingError::internal(
+                "expected a delta format action, found a parquet format action",
+            )),
+        }
+    }
+}
+
+fn file_sort_key(action: &TableAction) -> Option<(Option<u64>, &str)> {
+    action.as_file().map(|file| (file.version(), file.id()))
+}
+
+/// Capabilities advertised by a Delta Sharing server through the
+/// `delta-sharing-capabilities` response header.
+///
+/// The header is a `;` separated list of `key=value,value` pairs, e.g.
+/// `responseformat=parquet,delta;readerfeatures=deletionvectors`. Servers that
+/// do not send the header only support the parquet response format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    response_formats: Vec<String>,
+    reader_features: Vec<String>,
+}
+
+impl Capabilities {
+    /// Parse the value of the `delta-sharing-capabilities` header.
+    pub(crate) fn from_header(header: Option<&str>) -> Self {
+        let mut response_formats = vec![];
+        let mut reader_features = vec![];
+        for capability in header.unwrap_or_default().split(';') {
+            let Some((key, values)) = capability.split_once('=') else {
+                continue;
+            };
+            let values = values
+                .split(',')
+                .map(|v| v.trim().to_ascii_lowercase())
+                .filter(|v| !v.is_empty());
+            match key.trim().to_ascii_lowercase().as_str() {
+                "responseformat" => response_formats.extend(values),
+                "readerfeatures" => reader_features.extend(values),
+                _ => {}
+            }
+        }
+        if response_formats.is_empty() {
+            response_formats.push(String::from("parquet"));
+        }
+
+        Self {
+            response_formats,
+            reader_features,
+        }
+    }
+
+    /// Retrieve the response formats supported by the server, in lowercase
+    pub fn response_formats(&self) -> &[String] {
+        &self.response_formats
+    }
+
+    /// Retrieve the reader features supported by the server, in lowercase
+    pub fn reader_features(&self) -> &[String] {
+        &self.reader_features
+    }
+
+    /// Check whether the server supports a response format, e.g. `delta`
+    pub fn supports_response_format(&self, format: &str) -> bool {
+        self.response_formats
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(format))
+    }
+
+    /// Fail with a clear error if the server does not support a response
+    /// format.
+    pub fn require_response_format(&self, format: &str) -> Result<()> {
+        if self.supports_response_format(format) {
+            return Ok(());
+        }
+        tracing::error!(format, "response format not supported by server");
+        Err(DeltaSharingError::request(format!(
+            "feature not supported by this server: responseformat={format}"
+        )))
+    }
+}
+
+/// Highest table reader versions this client is able to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientReaderCapabilities {
+    parquet_reader_version: u32,
+    delta_reader_version: u32,
+    delta_reader_features: &'static [&'static str],
+}
+
+/// Reader capabilities of this client.
+pub const CLIENT_READER_CAPABILITIES: ClientReaderCapabilities = ClientReaderCapabilities {
+    parquet_reader_version: 1,
+    delta_reader_version: 3,
+    delta_reader_features: &[delta::READER_FEATURE_DELETION_VECTORS, "timestampNtz"],
+};
+
+impl ClientReaderCapabilities {
+    /// Retrieve the highest supported reader version for the parquet format
+    pub fn parquet_reader_version(&self) -> u32 {
+        self.parquet_reader_version
+    }
+
+    /// Retrieve the highest supported reader version for the delta format
+    pub fn delta_reader_version(&self) -> u32 {
+        self.delta_reader_version
+    }
+
+    /// Retrieve the supported reader features for the delta format
+    pub fn delta_reader_features(&self) -> &[&str] {
+        self.delta_reader_features
+    }
+
+    /// Check whether a protocol action can be read with these capabilities.
+    pub fn check(&self, protocol: &TableAction) -> Result<()> {
+        let (required, supported) = match protocol {
+            TableAction::Parquet(ParquetAction::Protocol(p)) => (
+                i64::from(p.min_reader_version()),
+                self.parquet_reader_version,
+            ),
+            TableAction::Delta(DeltaAction::Protocol(p)) => {
+                // Reader version 2 predates reader features and implies
+                // column mapping.
+                let implied =
+                    (p.min_reader_version() == 2).then_some(delta::READER_FEATURE_COLUMN_MAPPING);
+                if let Some(feature) = p
+                    .reader_features()
+                    .iter()
+                    .map(String::as_str)
+                    .chain(implied)
+                    .find(|f| !self.delta_reader_features.contains(f))
+                {
+                    tracing::error!(feature, "unsupported table reader feature");
+                    return Err(DeltaSharingError::parse_response(format!(
+                        "unsupported protocol: server requires reader feature {feature}"
+                    )));
+                }
+                (i64::from(p.min_reader_version()), self.delta_reader_version)
+            }
+            _ => {
+                return Err(DeltaSharingError::parse_response(
+                    "expected a protocol action",
+                ))
+            }
+        };
+
+        if required > i64::from(supported) {
+            tracing::error!(required, supported, "unsupported table protocol");
+            return Err(DeltaSharingError::parse_response(format!(
+                "unsupported protocol: server requires reader version {required}, client supports {supported}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn is_enabled(property: Option<&str>) -> bool {
+    property.is_some_and(|v| v.eq_ignore_ascii_case("true"))
+}
+
+/// Action ending a page of a paginated data query.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EndStreamAction {
+    next_page_token: Option<String>,
+}
+
+impl EndStreamAction {
+    pub(crate) fn into_next_page_token(self) -> Option<String> {
+        self.next_page_token
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EndStreamLine {
+    end_stream_action: EndStreamAction,
+}
+
+/// Parse a line of a newline delimited JSON body as an end stream action.
+pub(crate) fn parse_end_stream_action(line: &[u8]) -> Option<EndStreamAction> {
+    serde_json::from_slice::<EndStreamLine>(line)
+        .ok()
+        .map(|l| l.end_stream_action)
+}
+
+/// Split the trailing end stream action, if any, off a newline delimited JSON
+/// body.
+fn split_end_stream_action(body: &str) -> (&str, Option<EndStreamAction>) {
+    let body = strip_bom(body);
+    let trimmed = body.trim_end();
+    let start = trimmed.rfind('\n').map_or(0, |i| i + 1);
+    match parse_end_stream_action(trimmed[start..].as_bytes()) {
+        Some(end_stream) => (&trimmed[..start], Some(end_stream)),
+        None => (body, None),
+    }
+}
+
+/// Strip the UTF-8 byte order mark some servers and object stores prefix a
+/// body with.
+fn strip_bom(body: &str) -> &str {
+    body.strip_prefix('\u{feff}').unwrap_or(body)
+}
+
+/// Parse the actions of a newline delimited JSON body. Both `\n` and `\r\n`
+/// line endings are accepted and empty lines are skipped.
+fn parse_ndjson_actions(body: &str, strict: bool) -> Result<Vec<TableAction>> {
+    strip_bom(body)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| parse_action(line.as_bytes(), strict))
+        .collect()
+}
+
+/// Parse a single line of a newline delimited JSON body into an action.
+pub(crate) fn parse_action(line: &[u8], strict: bool) -> Result<TableAction> {
+    parse_table_action(line, strict).map_err(|e| {
+        tracing::error!(err = ?e, "failed to parse table action");
+        match e {
+            ParseJsonError::Malformed(e) => {
+                DeltaSharingError::parse_response("failed to parse table action").with_source(e)
+            }
+            e => e.into(),
+        }
+    })
+}
+
+fn parse_table_action(
+    line: &[u8],
+    strict: bool,
+) -> std::result::Result<TableAction, ParseJsonError> {
+    if !strict {
+        return parse_json(line, false);
+    }
+
+    // The untagged `TableAction` buffers its input, which hides the fields
+    // it ignores, so the formats are tried one after another instead.
+    let action = match parse_json::<ParquetAction>(line, true) {
+        Err(ParseJsonError::Malformed(_)) => {
+            parse_json::<DeltaAction>(line, true).map(TableAction::Delta)
+        }
+        action => action.map(TableAction::Parquet),
+    };
+    action.map_err(|e| match e {
+        // The field path does not include the action type, e.g. `protocol`.
+        ParseJsonError::UnknownField(field) => match action_type(line) {
+            Some(action_type) => ParseJsonError::UnknownField(format!("{action_type}.{field}")),
+            None => ParseJsonError::UnknownField(field),
+        },
+        e => e,
+    })
+}
+
+fn action_type(line: &[u8]) -> Option<String> {
+    serde_json::from_slice::<BTreeMap<String, IgnoredAny>>(line)
+        .ok()?
+        .into_keys()
+        .next()
+}
+
+/// Reason why a JSON document could not be parsed into a response type.
+#[derive(Debug)]
+pub(crate) enum ParseJsonError {
+    Malformed(serde_json::Error),
+    UnknownField(String),
+}
+
+impl From<ParseJsonError> for DeltaSharingError {
+    fn from(e: ParseJsonError) -> Self {
+        match e {
+            ParseJsonError::Malformed(e) => {
+                DeltaSharingError::parse_response("failed to parse server response").with_source(e)
+            }
+            ParseJsonError::UnknownField(field) => DeltaSharingError::parse_response(format!(
+                "unknown field `{field}` in server response"
+            )),
+        }
+    }
+}
+
+/// Parse a JSON document into a response type.
+///
+/// Unknown fields are ignored, unless `strict` is set. In strict mode the
+/// first field the deserializer ignores is reported as unknown.
+pub(crate) fn parse_json<T: DeserializeOwned>(
+    json: &[u8],
+    strict: bool,
+) -> std::result::Result<T, ParseJsonError> {
+    if !strict {
+        return serde_json::from_slice(json).map_err(ParseJsonError::Malformed);
+    }
+
+    let mut unknown = None;
+    let mut deserializer = serde_json::Deserializer::from_slice(json);
+    let parsed = serde_ignored::deserialize(&mut deserializer, |path| {
+        unknown.get_or_insert_with(|| field_path(&path));
+    })
+    .map_err(ParseJsonError::Malformed)?;
+    deserializer.end().map_err(ParseJsonError::Malformed)?;
+    match unknown {
+        Some(field) => Err(ParseJsonError::UnknownField(field)),
+        None => Ok(parsed),
+    }
+}
+
+/// Format the path of an ignored field as its map keys separated by dots,
+/// e.g. `items.owner`.
+fn field_path(path: &serde_ignored::Path) -> String {
+    let mut keys = vec![];
+    let mut path = path;
+    loop {
+        path = match path {
+            serde_ignored::Path::Root => break,
+            serde_ignored::Path::Map { parent, key } => {
+                keys.push(key.as_str());
+                *parent
+            }
+            serde_ignored::Path::Seq { parent, .. }
+            | serde_ignored::Path::Some { parent }
+            | serde_ignored::Path::NewtypeStruct { parent }
+            | serde_ignored::Path::NewtypeVariant { parent } => *parent,
+        };
+    }
+    keys.reverse();
+    keys.join(".")
+}
+
+fn next_protocol(actions: &mut impl Iterator<Item = TableAction>) -> Result<TableAction> {
+    let protocol = actions.next().filter(TableAction::is_protocol).ok_or(
+        DeltaSharingError::parse_response("expected protocol action in response"),
+    )?;
+    CLIENT_READER_CAPABILITIES.check(&protocol)?;
+    Ok(protocol)
+}
+
+fn next_metadata(actions: &mut impl Iterator<Item = TableAction>) -> Result<TableAction> {
+    actions
+        .next()
+        .filter(TableAction::is_metadata)
+        .ok_or(DeltaSharingError::parse_response(
+            "expected metadata action in response",
+        ))
+}
+
 /// Delta Sharing server response for failed requests.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -180,3 +1202,583 @@ impl fmt::Display for ErrorResponse {
         write!(f, "[{}] {}", self.error_code, self.message)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    fn table(share: &str, schema: &str, name: &str) -> Table {
+        Table {
+            share: share.to_owned(),
+            schema: schema.to_owned(),
+            name: name.to_owned(),
+            share_id: None,
+            id: None,
+        }
+    }
+
+    #[test]
+    fn order_tables_by_share_schema_name() {
+        let tables = BTreeSet::from([
+            table("b", "a", "a"),
+            table("a", "b", "a"),
+            table("a", "a", "b"),
+            table("a", "a", "a"),
+        ]);
+
+        let ordered = tables.into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            ordered,
+            vec![
+                table("a", "a", "a"),
+                table("a", "a", "b"),
+                table("a", "b", "a"),
+                table("b", "a", "a"),
+            ]
+        );
+    }
+
+    // Responses as documented in the Delta Sharing protocol.
+    const LIST_SHARES: &str = r#"{"items":[{"name":"vaccine_share","id":"edacc4a7-6600-4fbb-85f3-a62a5ce6761f"},{"name":"sales_share","id":"3e979c79-6399-4dac-bcf8-54e268f48515"}],"nextPageToken":"387e0bd9-47a4-4843-9d60-2bae2e9fdbc3"}"#;
+    const LIST_SCHEMAS: &str = r#"{"items":[{"name":"acme_vaccine_data","share":"vaccine_share"}],"nextPageToken":"3e979c79-6399-4dac-bcf8-54e268f48515"}"#;
+    const LIST_TABLES: &str = r#"{"items":[{"name":"vaccine_ingredients","schema":"acme_vaccine_data","share":"vaccine_share","shareId":"edacc4a7-6600-4fbb-85f3-a62a5ce6761f","id":"dcb1e680-7da4-4041-9be8-88aff508d001"}],"nextPageToken":"3e979c79-6399-4dac-bcf8-54e268f48515"}"#;
+
+    fn assert_round_trip<T: DeserializeOwned + Serialize>(json: &str) -> T {
+        let parsed = serde_json::from_str::<T>(json).unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::from_str::<Value>(json).unwrap()
+        );
+        parsed
+    }
+
+    #[test]
+    fn table_metadata_from_response() {
+        let body = format!(
+            "{{\"protocol\":{{\"minReaderVersion\":1}}}}\n{}",
+            r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet"},"schemaString":"{\"type\":\"struct\",\"fields\":[{\"name\":\"id\",\"type\":\"long\",\"nullable\":true,\"metadata\":{}},{\"name\":\"date\",\"type\":\"date\",\"nullable\":true,\"metadata\":{}}]}","partitionColumns":["date"],"configuration":{"delta.enableChangeDataFeed":"true"}}}"#
+        );
+        let response = QueryTableMetadataResponse::from_ndjson(3, &body, false).unwrap();
+        let metadata = TableMetadata::try_from(response).unwrap();
+
+        assert_eq!(metadata.version(), 3);
+        assert_eq!(metadata.partition_columns(), ["date"]);
+        assert_eq!(metadata.schema().fields().into_iter().count(), 2);
+        assert!(metadata.schema().field("date").is_some());
+        assert!(metadata.change_data_feed_enabled());
+    }
+
+    #[test]
+    fn deserialize_table_ids() {
+        let table = serde_json::from_str::<Table>(
+            r#"{"name":"t","schema":"d","share":"s","shareId":"sid","id":"tid"}"#,
+        )
+        .unwrap();
+        assert_eq!(table.share_id(), Some("sid"));
+        assert_eq!(table.id(), Some("tid"));
+
+        let table =
+            serde_json::from_str::<Table>(r#"{"name":"t","schema":"d","share":"s"}"#).unwrap();
+        assert_eq!(table.share_id(), None);
+        assert_eq!(table.id(), None);
+    }
+
+    #[test]
+    fn construct_query_responses() {
+        let protocol = parse_action(br#"{"protocol":{"minReaderVersion":1}}"#, false).unwrap();
+        let metadata = parse_action(PARQUET_METADATA.as_bytes(), false).unwrap();
+        let file = serde_json::from_str::<TableAction>(
+            r#"{"file":{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{},"size":573}}"#,
+        )
+        .unwrap();
+
+        let response =
+            QueryTableDataResponse::new(3, protocol.clone(), metadata.clone(), vec![file]);
+        let cloned = response.clone();
+        assert_eq!(cloned.version(), 3);
+        assert!(cloned.protocol().is_protocol());
+        assert!(cloned.metadata().is_metadata());
+        assert_eq!(cloned.files().len(), 1);
+
+        let response = QueryTableMetadataResponse::new(3, protocol, metadata);
+        assert_eq!(response.clone().version(), 3);
+        assert_eq!(QueryTableVersionResponse::new(3).version(), 3);
+    }
+
+    #[test]
+    fn data_response_file_totals() {
+        let protocol = parse_action(br#"{"protocol":{"minReaderVersion":1}}"#, false).unwrap();
+        let metadata = parse_action(PARQUET_METADATA.as_bytes(), false).unwrap();
+        let files = [("c1", 573), ("c2", 1024), ("c3", 0)]
+            .into_iter()
+            .map(|(id, size)| {
+                serde_json::from_str::<TableAction>(&format!(
+                    r#"{{"file":{{"url":"https://example.com/{id}.parquet","id":"{id}","partitionValues":{{}},"size":{size}}}}}"#
+                ))
+                .unwrap()
+            })
+            .collect();
+
+        let response = QueryTableDataResponse::new(3, protocol.clone(), metadata.clone(), files);
+        assert_eq!(response.file_count(), 3);
+        assert_eq!(response.total_size(), 1597);
+
+        let response = QueryTableDataResponse::new(3, protocol, metadata, vec![]);
+        assert_eq!(response.file_count(), 0);
+        assert_eq!(response.total_size(), 0);
+    }
+
+    #[test]
+    fn visit_actions() {
+        #[derive(Default)]
+        struct CountingVisitor {
+            protocols: usize,
+            metadata: usize,
+            files: usize,
+            removes: usize,
+            cdcs: usize,
+        }
+
+        impl ActionVisitor for CountingVisitor {
+            fn on_protocol(&mut self, _action: &TableAction) {
+                self.protocols += 1;
+            }
+
+            fn on_metadata(&mut self, _action: &TableAction) {
+                self.metadata += 1;
+            }
+
+            fn on_file(&mut self, _file: &dyn FileAction) {
+                self.files += 1;
+            }
+
+            fn on_remove(&mut self, _file: &dyn FileAction) {
+                self.removes += 1;
+            }
+
+            fn on_cdc(&mut self, _file: &dyn FileAction) {
+                self.cdcs += 1;
+            }
+        }
+
+        let file = r#"{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{},"size":573}"#;
+        let actions = [
+            r#"{"protocol":{"minReaderVersion":1}}"#.to_owned(),
+            PARQUET_METADATA.to_owned(),
+            format!(r#"{{"file":{file}}}"#),
+            format!(r#"{{"add":{file}}}"#),
+            format!(r#"{{"remove":{file}}}"#),
+            format!(r#"{{"cdf":{file}}}"#),
+            r#"{"file":{"id":"c2","deltaSingleAction":{"add":{"path":"https://example.com/part-1.parquet","partitionValues":{},"size":1024,"modificationTime":1619591469000,"dataChange":true}}}}"#.to_owned(),
+        ];
+
+        let mut visitor = CountingVisitor::default();
+        for action in &actions {
+            serde_json::from_str::<TableAction>(action)
+                .unwrap()
+                .visit(&mut visitor);
+        }
+        assert_eq!(visitor.protocols, 1);
+        assert_eq!(visitor.metadata, 1);
+        assert_eq!(visitor.files, 3);
+        assert_eq!(visitor.removes, 1);
+        assert_eq!(visitor.cdcs, 1);
+    }
+
+    #[test]
+    fn round_trip_list_responses() {
+        let shares = assert_round_trip::<ListSharesResponse>(LIST_SHARES);
+        assert_eq!(shares.items()[1].name(), "sales_share");
+        assert_eq!(
+            shares.next_page_token(),
+            Some("387e0bd9-47a4-4843-9d60-2bae2e9fdbc3")
+        );
+
+        let schemas = assert_round_trip::<ListSchemasResponse>(LIST_SCHEMAS);
+        assert_eq!(schemas.items()[0].share(), "vaccine_share");
+
+        let tables = assert_round_trip::<ListTablesResponse>(LIST_TABLES);
+        let table = &tables.items()[0];
+        assert_eq!(table.schema(), "acme_vaccine_data");
+        assert_eq!(
+            table.share_id(),
+            Some("edacc4a7-6600-4fbb-85f3-a62a5ce6761f")
+        );
+        assert_eq!(table.id(), Some("dcb1e680-7da4-4041-9be8-88aff508d001"));
+    }
+
+    #[test]
+    fn build_list_response() {
+        let response = ListResponse::new(vec![1, 2], Some(String::from("next")));
+        assert_eq!(response.items(), [1, 2]);
+        assert_eq!(response.next_page_token(), Some("next"));
+
+        let mut response = (1..=2).collect::<ListResponse<_>>();
+        response.extend([3]);
+        assert_eq!(response.items(), [1, 2, 3]);
+        assert_eq!(response.next_page_token(), None);
+    }
+
+    #[test]
+    fn deserialize_list_total_count() {
+        let response = serde_json::from_str::<ListSharesResponse>(
+            r#"{"items":[{"name":"vaccine_share"}],"nextPageToken":"next","totalCount":3}"#,
+        )
+        .unwrap();
+        assert_eq!(response.total_count(), Some(3));
+
+        let response =
+            serde_json::from_str::<ListSharesResponse>(r#"{"items":[],"total":3}"#).unwrap();
+        assert_eq!(response.total_count(), Some(3));
+
+        let response = serde_json::from_str::<ListSharesResponse>(r#"{"items":[]}"#).unwrap();
+        assert_eq!(response.total_count(), None);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({"items": [], "nextPageToken": null})
+        );
+    }
+
+    #[test]
+    fn accept_list_total_count_spellings_in_strict_mode() {
+        for json in [
+            r#"{"items":[],"totalCount":3}"#,
+            r#"{"items":[],"total":3}"#,
+        ] {
+            let response = parse_json::<ListSharesResponse>(json.as_bytes(), true).unwrap();
+            assert_eq!(response.total_count(), Some(3));
+        }
+
+        let err = parse_json::<ListSharesResponse>(br#"{"items":[],"count":3}"#, true).unwrap_err();
+        assert!(matches!(err, ParseJsonError::UnknownField(field) if field == "count"));
+    }
+
+    #[test]
+    fn diff_table_snapshots() {
+        let before = BTreeSet::from([table("s", "d", "t1"), table("s", "d", "t2")]);
+        let after = BTreeSet::from([table("s", "d", "t2"), table("s", "d", "t3")]);
+
+        let added = after.difference(&before).collect::<Vec<_>>();
+        let removed = before.difference(&after).collect::<Vec<_>>();
+        assert_eq!(added, vec![&table("s", "d", "t3")]);
+        assert_eq!(removed, vec![&table("s", "d", "t1")]);
+    }
+
+    const PARQUET_METADATA: &str = r#"{"metaData":{"id":"f8d5c169-3d01-4ca3-ad9e-7dc3355aedb2","format":{"provider":"parquet"},"schemaString":"{\"type\":\"struct\",\"fields\":[]}","partitionColumns":[]}}"#;
+    const DELTA_METADATA: &str = r#"{"metaData":{"deltaMetadata":{"id":"f8d5c169-3d01-4ca3-ad9e-7dc3355aedb2","format":{"provider":"parquet","options":{}},"schemaString":"{\"type\":\"struct\",\"fields\":[]}","partitionColumns":[],"configuration":{},"createdTime":1619591469476}}}"#;
+
+    #[test]
+    fn rewrite_file_urls() {
+        let body = format!(
+            "{{\"protocol\":{{\"minReaderVersion\":1}}}}\n{PARQUET_METADATA}\n{}\n",
+            r#"{"file":{"url":"https://internal.host/part-0.parquet?sig=abc","id":"c1","partitionValues":{},"size":573}}"#
+        );
+        let mut response = QueryTableDataResponse::from_ndjson(1, &body, false).unwrap();
+
+        response.rewrite_file_urls(&|mut url: Url| {
+            if url.host_str() == Some("internal.host") {
+                url.set_host(Some("public.host")).unwrap();
+            }
+            url
+        });
+
+        let file = response.files()[0].as_parquet().unwrap();
+        let ParquetAction::File(file) = file else {
+            panic!("expected file action");
+        };
+        assert_eq!(file.url(), "https://public.host/part-0.parquet?sig=abc");
+    }
+
+    #[test]
+    fn access_files_of_both_formats() {
+        let parquet = serde_json::from_str::<TableAction>(
+            r#"{"file":{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{"date":"2021-04-28"},"size":573,"expirationTimestamp":1652140800000}}"#,
+        )
+        .unwrap();
+        let delta = serde_json::from_str::<TableAction>(
+            r#"{"file":{"id":"c2","expirationTimestamp":1652140800000,"deltaSingleAction":{"add":{"path":"https://example.com/part-1.parquet","partitionValues":{"date":"2021-04-28"},"size":1024,"modificationTime":1619591469000,"dataChange":true}}}}"#,
+        )
+        .unwrap();
+        assert!(parquet.is_parquet());
+        assert!(delta.is_delta());
+
+        let files = [&parquet, &delta]
+            .into_iter()
+            .map(|action| action.as_file().unwrap())
+            .map(|file| (file.id(), file.url(), file.size()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                ("c1", "https://example.com/part-0.parquet", 573),
+                ("c2", "https://example.com/part-1.parquet", 1024),
+            ]
+        );
+
+        for action in [&parquet, &delta] {
+            let file = action.as_file().unwrap();
+            assert_eq!(
+                file.partition_values().get("date"),
+                Some(&Some(String::from("2021-04-28")))
+            );
+            assert_eq!(file.stats(), None);
+            assert_eq!(
+                file.expiration_time(),
+                DateTime::from_timestamp_millis(1652140800000)
+            );
+        }
+
+        let protocol =
+            serde_json::from_str::<TableAction>(r#"{"protocol":{"minReaderVersion":1}}"#).unwrap();
+        assert!(protocol.as_file().is_none());
+    }
+
+    #[test]
+    fn group_changes_by_version() {
+        let change = |kind: &str, id: &str, version: u64| {
+            format!(
+                r#"{{"{kind}":{{"url":"https://example.com/{id}.parquet","id":"{id}","partitionValues":{{}},"size":573,"version":{version},"timestamp":1652140800000}}}}"#
+            )
+        };
+        let body = [
+            String::from(r#"{"protocol":{"minReaderVersion":1}}"#),
+            String::from(PARQUET_METADATA),
+            change("add", "a1", 1),
+            change("cdf", "c2", 2),
+            change("add", "a3", 3),
+            change("remove", "r1", 1),
+            change("cdf", "c3", 3),
+        ]
+        .join("\n");
+        let response = QueryTableChangesResponse::from_ndjson(3, &body, false).unwrap();
+
+        let ids = response
+            .by_version()
+            .into_iter()
+            .map(|(version, actions)| {
+                let ids = actions
+                    .iter()
+                    .map(|a| a.as_file().unwrap().id())
+                    .collect::<Vec<_>>();
+                (version, ids)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                (1, vec!["a1", "r1"]),
+                (2, vec!["c2"]),
+                (3, vec!["a3", "c3"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn convert_between_table_action_and_inner_actions() {
+        let line = r#"{"protocol":{"minReaderVersion":1}}"#;
+        let parquet = serde_json::from_str::<ParquetAction>(line).unwrap();
+
+        let action = TableAction::from(parquet);
+        assert!(action.is_parquet());
+
+        let err = DeltaAction::try_from(action.clone()).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "expected a delta format action, found a parquet format action"
+        );
+        let parquet = ParquetAction::try_from(action).unwrap();
+        assert!(parquet.is_protocol());
+
+        let line = r#"{"protocol":{"deltaProtocol":{"minReaderVersion":1,"minWriterVersion":2}}}"#;
+        let delta = serde_json::from_str::<DeltaAction>(line).unwrap();
+
+        let action = TableAction::from(delta);
+        assert!(action.is_delta());
+
+        let err = ParquetAction::try_from(action.clone()).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "expected a parquet format action, found a delta format action"
+        );
+        let delta = DeltaAction::try_from(action).unwrap();
+        assert!(delta.is_protocol());
+    }
+
+    #[test]
+    fn parse_ndjson_with_bom_and_crlf() {
+        let body = format!(
+            "\u{feff}{{\"protocol\":{{\"minReaderVersion\":1}}}}\r\n\r\n{PARQUET_METADATA}\r\n"
+        );
+        let response = QueryTableMetadataResponse::from_ndjson(1, &body, false).unwrap();
+        assert!(response.protocol.is_protocol());
+        assert!(response.metadata.is_metadata());
+
+        let body = format!(
+            "\u{feff}{{\"protocol\":{{\"minReaderVersion\":1}}}}\r\n{PARQUET_METADATA}\r\n{}\r\n{}\r\n",
+            r#"{"file":{"url":"https://example.com/part-0.parquet","id":"f0","partitionValues":{},"size":573}}"#,
+            r#"{"endStreamAction":{"nextPageToken":"page-2"}}"#,
+        );
+        let response = QueryTableDataResponse::from_ndjson(1, &body, false).unwrap();
+        assert_eq!(response.files().len(), 1);
+        assert_eq!(response.next_page_token(), Some("page-2"));
+    }
+
+    #[test]
+    fn parse_supported_parquet_protocol() {
+        let body = format!("{{\"protocol\":{{\"minReaderVersion\":1}}}}\n{PARQUET_METADATA}\n");
+
+        let response = QueryTableMetadataResponse::from_ndjson(1, &body, false).unwrap();
+        assert!(response.protocol.is_parquet());
+        assert!(response.metadata.is_metadata());
+    }
+
+    #[test]
+    fn reject_unsupported_parquet_protocol() {
+        let body = format!("{{\"protocol\":{{\"minReaderVersion\":2}}}}\n{PARQUET_METADATA}\n");
+
+        let err = QueryTableMetadataResponse::from_ndjson(1, &body, false).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unsupported protocol: server requires reader version 2, client supports 1"
+        );
+    }
+
+    #[test]
+    fn parse_supported_delta_protocol() {
+        let body = format!("{{\"protocol\":{{\"deltaProtocol\":{{\"minReaderVersion\":3,\"minWriterVersion\":7}}}}}}\n{DELTA_METADATA}\n");
+
+        let response = QueryTableDataResponse::from_ndjson(1, &body, false).unwrap();
+        assert!(response.protocol.is_delta());
+        assert!(response.files.is_empty());
+    }
+
+    #[test]
+    fn reject_unsupported_delta_protocol() {
+        let body = format!("{{\"protocol\":{{\"deltaProtocol\":{{\"minReaderVersion\":4,\"minWriterVersion\":7}}}}}}\n{DELTA_METADATA}\n");
+
+        let err = QueryTableDataResponse::from_ndjson(1, &body, false).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unsupported protocol: server requires reader version 4, client supports 3"
+        );
+    }
+
+    #[test]
+    fn reject_unknown_action_fields_in_strict_mode() {
+        let body = format!(
+            "{{\"protocol\":{{\"minReaderVersion\":1,\"minWriterVersion\":2}}}}\n{PARQUET_METADATA}\n"
+        );
+
+        let response = QueryTableMetadataResponse::from_ndjson(1, &body, false).unwrap();
+        assert!(response.protocol().is_protocol());
+
+        let err = QueryTableMetadataResponse::from_ndjson(1, &body, true).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unknown field `protocol.minWriterVersion` in server response"
+        );
+
+        let body = format!("{{\"protocol\":{{\"minReaderVersion\":1}}}}\n{PARQUET_METADATA}\n");
+        assert!(QueryTableMetadataResponse::from_ndjson(1, &body, true).is_ok());
+    }
+
+    #[test]
+    fn accept_explicit_nulls_in_strict_mode() {
+        let body = format!("{{\"protocol\":{{\"deltaProtocol\":{{\"minReaderVersion\":1,\"minWriterVersion\":2,\"readerFeatures\":null,\"writerFeatures\":null}}}}}}\n{DELTA_METADATA}\n");
+
+        let response = QueryTableMetadataResponse::from_ndjson(1, &body, true).unwrap();
+        assert!(response.protocol().is_protocol());
+    }
+
+    #[test]
+    fn keep_serde_error_as_source_in_strict_mode() {
+        let body =
+            format!("{{\"protocol\":{{\"minReaderVersion\":\"one\"}}}}\n{PARQUET_METADATA}\n");
+
+        let err = QueryTableMetadataResponse::from_ndjson(1, &body, true).unwrap_err();
+        assert_eq!(err.message(), "failed to parse table action");
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn convert_parquet_metadata_to_kernel_metadata() {
+        let body = format!("{{\"protocol\":{{\"minReaderVersion\":1}}}}\n{PARQUET_METADATA}\n");
+        let response = QueryTableMetadataResponse::from_ndjson(1, &body, false).unwrap();
+
+        let metadata = response.into_kernel_metadata().unwrap();
+        assert_eq!(metadata.id, "f8d5c169-3d01-4ca3-ad9e-7dc3355aedb2");
+        assert_eq!(metadata.format.provider, "parquet");
+        assert_eq!(metadata.schema_string, r#"{"type":"struct","fields":[]}"#);
+        assert!(metadata.partition_columns.is_empty());
+        assert_eq!(metadata.created_time, None);
+    }
+
+    #[test]
+    fn convert_delta_metadata_to_kernel_metadata() {
+        let body = format!("{{\"protocol\":{{\"deltaProtocol\":{{\"minReaderVersion\":1,\"minWriterVersion\":2}}}}}}\n{DELTA_METADATA}\n");
+        let response = QueryTableMetadataResponse::from_ndjson(1, &body, false).unwrap();
+
+        let metadata = response.into_kernel_metadata().unwrap();
+        assert_eq!(metadata.id, "f8d5c169-3d01-4ca3-ad9e-7dc3355aedb2");
+        assert_eq!(metadata.format.provider, "parquet");
+        assert_eq!(metadata.created_time, Some(1619591469476));
+    }
+
+    #[test]
+    fn parse_server_capabilities() {
+        let capabilities = Capabilities::from_header(Some(
+            "responseFormat=parquet,delta;readerFeatures=deletionVectors",
+        ));
+        assert_eq!(capabilities.response_formats(), ["parquet", "delta"]);
+        assert_eq!(capabilities.reader_features(), ["deletionvectors"]);
+        assert!(capabilities.require_response_format("delta").is_ok());
+
+        let capabilities = Capabilities::from_header(None);
+        assert_eq!(capabilities.response_formats(), ["parquet"]);
+        assert!(capabilities.reader_features().is_empty());
+    }
+
+    #[test]
+    fn inspect_delta_reader_features() {
+        let action = serde_json::from_str::<TableAction>(
+            r#"{"protocol":{"deltaProtocol":{"minReaderVersion":3,"minWriterVersion":7,"readerFeatures":["columnMapping","deletionVectors"],"writerFeatures":[]}}}"#,
+        )
+        .unwrap();
+
+        let Some(DeltaAction::Protocol(protocol)) = action.as_delta() else {
+            panic!("expected a delta protocol action");
+        };
+        assert_eq!(protocol.min_writer_version(), 7);
+        assert_eq!(
+            protocol.reader_features(),
+            ["columnMapping", "deletionVectors"]
+        );
+        assert!(protocol.requires_deletion_vectors());
+    }
+
+    #[test]
+    fn reject_unsupported_delta_reader_feature() {
+        let body = format!("{{\"protocol\":{{\"deltaProtocol\":{{\"minReaderVersion\":3,\"minWriterVersion\":7,\"readerFeatures\":[\"columnMapping\"],\"writerFeatures\":[\"columnMapping\"]}}}}}}\n{DELTA_METADATA}\n");
+
+        let err = QueryTableDataResponse::from_ndjson(1, &body, false).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unsupported protocol: server requires reader feature columnMapping"
+        );
+    }
+
+    #[test]
+    fn reject_legacy_column_mapping_protocol() {
+        let body = format!("{{\"protocol\":{{\"deltaProtocol\":{{\"minReaderVersion\":2,\"minWriterVersion\":5}}}}}}\n{DELTA_METADATA}\n");
+
+        let err = QueryTableDataResponse::from_ndjson(1, &body, false).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unsupported protocol: server requires reader feature columnMapping"
+        );
+    }
+}
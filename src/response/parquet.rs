@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::FileAction;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum ParquetAction {
@@ -9,6 +12,9 @@ pub enum ParquetAction {
     #[serde(rename = "metaData")]
     Metadata(ParquetMetadataAction),
     File(ParquetFileAction),
+    Add(ParquetFileAction),
+    Cdf(ParquetFileAction),
+    Remove(ParquetFileAction),
 }
 
 impl ParquetAction {
@@ -20,8 +26,32 @@ impl ParquetAction {
         matches!(self, ParquetAction::Metadata(_))
     }
 
+    /// Check whether the action references a file, i.e. it is a `file`
+    /// action of a data query or an `add`, `cdf` or `remove` action of a
+    /// changes query.
     pub fn is_file(&self) -> bool {
-        matches!(self, ParquetAction::File(_))
+        self.as_file().is_some()
+    }
+
+    /// Retrieve the file referenced by the action, if any
+    pub fn as_file(&self) -> Option<&ParquetFileAction> {
+        match self {
+            ParquetAction::File(f)
+            | ParquetAction::Add(f)
+            | ParquetAction::Cdf(f)
+            | ParquetAction::Remove(f) => Some(f),
+            ParquetAction::Protocol(_) | ParquetAction::Metadata(_) => None,
+        }
+    }
+
+    pub(crate) fn as_file_mut(&mut self) -> Option<&mut ParquetFileAction> {
+        match self {
+            ParquetAction::File(f)
+            | ParquetAction::Add(f)
+            | ParquetAction::Cdf(f)
+            | ParquetAction::Remove(f) => Some(f),
+            ParquetAction::Protocol(_) | ParquetAction::Metadata(_) => None,
+        }
     }
 }
 
@@ -31,13 +61,21 @@ pub struct ParquetProtocolAction {
     min_reader_version: u32,
 }
 
+impl ParquetProtocolAction {
+    /// Retrieve the minimum reader version required to read the table
+    pub fn min_reader_version(&self) -> u32 {
+        self.min_reader_version
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ParquetMetadataAction {
     id: String,
     name: Option<String>,
     description: Option<String>,
-    // format: ParquetResponseFormat,
+    #[serde(default)]
+    format: Format,
     schema_string: String,
     partition_columns: Vec<String>,
     #[serde(default)]
@@ -47,6 +85,83 @@ pub struct ParquetMetadataAction {
     num_files: Option<u64>,
 }
 
+impl ParquetMetadataAction {
+    /// Retrieve the Arrow schema of the table, including partition columns.
+    #[cfg(feature = "arrow")]
+    pub fn arrow_schema(&self) -> crate::Result<arrow_schema::SchemaRef> {
+        crate::arrow::arrow_schema_from_schema_string(&self.schema_string)
+    }
+
+    /// Retrieve the format of the files in the table
+    pub fn format(&self) -> &Format {
+        &self.format
+    }
+
+    /// Retrieve the table properties of the table
+    pub fn configuration(&self) -> &HashMap<String, Option<String>> {
+        &self.configuration
+    }
+
+    /// Check whether the change data feed is enabled on the table
+    pub fn change_data_feed_enabled(&self) -> bool {
+        let property = self.configuration.get(super::TABLE_PROPERTY_ENABLE_CDF);
+        super::is_enabled(property.and_then(|v| v.as_deref()))
+    }
+}
+
+impl From<ParquetMetadataAction> for delta_kernel::actions::Metadata {
+    fn from(metadata: ParquetMetadataAction) -> Self {
+        Self {
+            id: metadata.id,
+            name: metadata.name,
+            description: metadata.description,
+            format: delta_kernel::actions::Format {
+                provider: metadata.format.provider,
+                options: metadata.format.options,
+            },
+            schema_string: metadata.schema_string,
+            partition_columns: metadata.partition_columns,
+            created_time: None,
+            configuration: metadata.configuration,
+        }
+    }
+}
+
+/// Format of the files in a table as declared in its metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Format {
+    #[serde(default = "default_format_provider")]
+    provider: String,
+    #[serde(default)]
+    options: HashMap<String, String>,
+}
+
+impl Format {
+    /// Retrieve the name of the file format, e.g. `parquet`
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    /// Retrieve the options of the file format
+    pub fn options(&self) -> &HashMap<String, String> {
+        &self.options
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            provider: default_format_provider(),
+            options: HashMap::new(),
+        }
+    }
+}
+
+fn default_format_provider() -> String {
+    String::from("parquet")
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ParquetFileAction {
@@ -59,3 +174,162 @@ pub struct ParquetFileAction {
     timestamp: Option<u64>,
     expiration_timestamp: Option<i64>,
 }
+
+impl ParquetFileAction {
+    /// Retrieve the presigned URL of the file
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub(crate) fn set_url(&mut self, url: String) {
+        self.url = url;
+    }
+
+    /// Retrieve the id of the file
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Retrieve the size of the file in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Retrieve the partition values of the file
+    pub fn partition_values(&self) -> &HashMap<String, Option<String>> {
+        &self.partition_values
+    }
+
+    /// Retrieve the statistics of the file as a JSON string, if available
+    pub fn stats(&self) -> Option<&str> {
+        self.stats.as_deref()
+    }
+
+    /// Retrieve the table version of the file, if returned by the server
+    pub fn version(&self) -> Option<u64> {
+        self.version
+    }
+
+    /// Retrieve the commit timestamp of the file, if returned by the server
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+            .and_then(|ts| i64::try_from(ts).ok())
+            .and_then(DateTime::from_timestamp_millis)
+    }
+}
+
+impl FileAction for ParquetFileAction {
+    fn url(&self) -> &str {
+        ParquetFileAction::url(self)
+    }
+
+    fn id(&self) -> &str {
+        ParquetFileAction::id(self)
+    }
+
+    fn size(&self) -> u64 {
+        ParquetFileAction::size(self)
+    }
+
+    fn partition_values(&self) -> &HashMap<String, Option<String>> {
+        ParquetFileAction::partition_values(self)
+    }
+
+    fn stats(&self) -> Option<&str> {
+        ParquetFileAction::stats(self)
+    }
+
+    fn version(&self) -> Option<u64> {
+        ParquetFileAction::version(self)
+    }
+
+    fn expiration_time(&self) -> Option<DateTime<Utc>> {
+        self.expiration_timestamp
+            .and_then(DateTime::from_timestamp_millis)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize_metadata_format() {
+        let line = r#"{"metaData":{"id":"f8d5c169","format":{"provider":"parquet","options":{"compression":"snappy"}},"schemaString":"{}","partitionColumns":[]}}"#;
+
+        let action = serde_json::from_str::<ParquetAction>(line).unwrap();
+        let ParquetAction::Metadata(metadata) = action else {
+            panic!("expected metadata action");
+        };
+        assert_eq!(metadata.format().provider(), "parquet");
+        assert_eq!(
+            metadata
+                .format()
+                .options()
+                .get("compression")
+                .map(String::as_str),
+            Some("snappy")
+        );
+    }
+
+    #[test]
+    fn file_action_accessors() {
+        let line = r#"{"file":{"url":"https://example.com/part-0.parquet","id":"c1","partitionValues":{"date":"2021-04-28"},"size":573,"stats":"{\"numRecords\":1}","version":3,"timestamp":1627776000123}}"#;
+
+        let action = serde_json::from_str::<ParquetAction>(line).unwrap();
+        let ParquetAction::File(file) = action else {
+            panic!("expected file action");
+        };
+        assert_eq!(file.id(), "c1");
+        assert_eq!(file.size(), 573);
+        assert_eq!(
+            file.partition_values().get("date"),
+            Some(&Some(String::from("2021-04-28")))
+        );
+        assert_eq!(file.stats(), Some(r#"{"numRecords":1}"#));
+        assert_eq!(file.version(), Some(3));
+        assert_eq!(
+            file.timestamp(),
+            DateTime::from_timestamp_millis(1627776000123)
+        );
+    }
+
+    #[test]
+    fn deserialize_metadata_default_format() {
+        let line = r#"{"metaData":{"id":"f8d5c169","format":{},"schemaString":"{}","partitionColumns":[]}}"#;
+        let action = serde_json::from_str::<ParquetAction>(line).unwrap();
+        let ParquetAction::Metadata(metadata) = action else {
+            panic!("expected metadata action");
+        };
+        assert_eq!(metadata.format(), &Format::default());
+
+        let line = r#"{"metaData":{"id":"f8d5c169","schemaString":"{}","partitionColumns":[]}}"#;
+        let action = serde_json::from_str::<ParquetAction>(line).unwrap();
+        let ParquetAction::Metadata(metadata) = action else {
+            panic!("expected metadata action");
+        };
+        assert_eq!(metadata.format().provider(), "parquet");
+    }
+
+    #[test]
+    fn metadata_configuration() {
+        let line = r#"{"metaData":{"id":"f8d5c169","schemaString":"{}","partitionColumns":[],"configuration":{"delta.enableChangeDataFeed":"true","delta.columnMapping.mode":"none"}}}"#;
+        let action = serde_json::from_str::<ParquetAction>(line).unwrap();
+        let ParquetAction::Metadata(metadata) = action else {
+            panic!("expected metadata action");
+        };
+        assert_eq!(
+            metadata.configuration().get("delta.columnMapping.mode"),
+            Some(&Some(String::from("none")))
+        );
+        assert!(metadata.change_data_feed_enabled());
+
+        let line = r#"{"metaData":{"id":"f8d5c169","schemaString":"{}","partitionColumns":[]}}"#;
+        let action = serde_json::from_str::<ParquetAction>(line).unwrap();
+        let ParquetAction::Metadata(metadata) = action else {
+            panic!("expected metadata action");
+        };
+        assert!(metadata.configuration().is_empty());
+        assert!(!metadata.change_data_feed_enabled());
+    }
+}
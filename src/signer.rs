@@ -0,0 +1,29 @@
+//! Hook to sign the requests made by the client.
+//!
+//! Some gateways in front of a Delta Sharing server require requests to be
+//! signed, e.g. with an HMAC of the path and a timestamp, in addition to the
+//! bearer token. Implement [`RequestSigner`] and register it with
+//! [`DeltaSharingClientBuilder::with_request_signer`](crate::DeltaSharingClientBuilder::with_request_signer).
+
+use std::fmt::Debug;
+
+use reqwest::Request;
+
+use crate::Result;
+
+/// Signs outgoing requests.
+pub trait RequestSigner: Debug + Send + Sync {
+    /// Sign a request right before it is sent, after the `Authorization`
+    /// header was set. Failing to sign fails the request.
+    fn sign(&self, request: &mut Request) -> Result<()>;
+}
+
+/// Signer that leaves requests untouched.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopSigner;
+
+impl RequestSigner for NoopSigner {
+    fn sign(&self, _request: &mut Request) -> Result<()> {
+        Ok(())
+    }
+}